@@ -0,0 +1,87 @@
+//! Collapse the records for each unique molecule into a single row
+
+use std::collections::HashMap;
+
+use clap::ValueEnum;
+use fftools::{
+    die,
+    stats::{weighted_mean, weighted_rms},
+};
+
+use crate::metrics::Row;
+
+#[cfg(test)]
+mod tests;
+
+/// How to combine the values of the conformers of a single molecule
+#[derive(Clone, Copy, ValueEnum)]
+pub(crate) enum Aggregate {
+    /// the weighted mean of the values
+    Mean,
+    /// the value with the largest magnitude, keeping its sign
+    Max,
+    /// the weighted root-mean-square of the values
+    Rms,
+}
+
+impl Aggregate {
+    fn apply(self, values: &[f64], weights: &[f64]) -> f64 {
+        match self {
+            Aggregate::Mean => weighted_mean(values, weights),
+            Aggregate::Max => values
+                .iter()
+                .copied()
+                .max_by(|a, b| a.abs().total_cmp(&b.abs()))
+                .unwrap(),
+            Aggregate::Rms => weighted_rms(values, weights),
+        }
+    }
+}
+
+/// Group `rows` by the SMILES of their molecules in `dataset` and combine each
+/// group into one row with `how`. Every metric is combined over the records
+/// that have a value for it, and the weight of the combined row is the mean
+/// weight of its records. The combined row keeps the ID of the first record in
+/// the group, so it can still be looked up in `dataset`.
+pub(crate) fn aggregate(
+    rows: Vec<Row>,
+    dataset: &HashMap<String, String>,
+    how: Aggregate,
+) -> Vec<Row> {
+    let mut groups: HashMap<&str, Vec<Row>> = HashMap::new();
+    let mut order = Vec::new();
+    for row in rows {
+        let Some(smiles) = dataset.get(&row.id.to_string()) else {
+            die!("record {} not found in dataset", row.id);
+        };
+        let group = groups.entry(smiles).or_default();
+        if group.is_empty() {
+            order.push(smiles.as_str());
+        }
+        group.push(row);
+    }
+
+    order
+        .into_iter()
+        .map(|smiles| {
+            let group = &groups[smiles];
+            let nmetrics = group[0].values.len();
+            let values = (0..nmetrics)
+                .map(|i| {
+                    let (vs, ws): (Vec<f64>, Vec<f64>) = group
+                        .iter()
+                        .filter_map(|r| Some((r.values[i]?, r.weight)))
+                        .unzip();
+                    (!vs.is_empty()).then(|| how.apply(&vs, &ws))
+                })
+                .collect();
+            let weight = group.iter().map(|r| r.weight).sum::<f64>()
+                / group.len() as f64;
+            Row {
+                id: group[0].id,
+                values,
+                weight,
+            }
+        })
+        .collect()
+}
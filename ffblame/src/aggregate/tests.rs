@@ -0,0 +1,59 @@
+use super::*;
+
+fn row(id: usize, values: &[Option<f64>], weight: f64) -> Row {
+    Row {
+        id,
+        values: values.to_vec(),
+        weight,
+    }
+}
+
+/// three conformers of ethane, one without a value for the second metric, and
+/// a single record of methane
+fn rows() -> (Vec<Row>, HashMap<String, String>) {
+    let rows = vec![
+        row(1, &[Some(1.0), Some(2.0)], 1.0),
+        row(2, &[Some(-3.0), None], 1.0),
+        row(3, &[Some(2.0), Some(4.0)], 2.0),
+        row(4, &[Some(5.0), None], 1.0),
+    ];
+    let dataset = [(1, "CC"), (2, "CC"), (3, "CC"), (4, "C")]
+        .into_iter()
+        .map(|(id, smiles)| (id.to_string(), smiles.to_owned()))
+        .collect();
+    (rows, dataset)
+}
+
+fn check(how: Aggregate, want: [f64; 2]) {
+    let (rows, dataset) = rows();
+    let got = aggregate(rows, &dataset, how);
+    assert_eq!(got.len(), 2);
+
+    let ethane = &got[0];
+    assert_eq!(ethane.id, 1);
+    assert!((ethane.weight - 4.0 / 3.0).abs() < 1e-12);
+    for (got, want) in ethane.values.iter().zip(want) {
+        assert!((got.unwrap() - want).abs() < 1e-12, "{got:?} != {want}");
+    }
+
+    let methane = &got[1];
+    assert_eq!((methane.id, methane.weight), (4, 1.0));
+    assert_eq!(methane.values, [Some(5.0), None]);
+}
+
+#[test]
+fn test_mean() {
+    // (1 - 3 + 2 * 2) / 4 and (2 + 2 * 4) / 3
+    check(Aggregate::Mean, [0.5, 10.0 / 3.0]);
+}
+
+#[test]
+fn test_max() {
+    check(Aggregate::Max, [-3.0, 4.0]);
+}
+
+#[test]
+fn test_rms() {
+    // sqrt((1 + 9 + 2 * 4) / 4) and sqrt((4 + 2 * 16) / 3)
+    check(Aggregate::Rms, [4.5f64.sqrt(), 12f64.sqrt()]);
+}
@@ -0,0 +1,110 @@
+//! Test parameters for over-representation among outlier records
+
+use std::collections::{HashMap, HashSet};
+
+use fftools::{
+    stats::{benjamini_hochberg, odds_ratio, LnFactorials},
+    Pid,
+};
+
+use crate::{errors_by_param, metrics::Row};
+
+/// One row of the enrichment table
+struct Enrichment {
+    pid: Pid,
+    /// weighted mean of the first metric
+    mean: f64,
+    /// number of records labeled with this parameter
+    count: usize,
+    /// number of outlier records labeled with this parameter
+    outliers: usize,
+    odds_ratio: f64,
+    pvalue: f64,
+    adjusted: f64,
+}
+
+/// Compare the fraction of outlier records (those with `|value| > threshold`
+/// for the first metric) labeled with each parameter to the fraction of all
+/// records labeled with it. Records without a value for the first metric are
+/// ignored. Each parameter gets a one-sided Fisher exact test, and the
+/// resulting p-values are corrected for multiple testing with the
+/// Benjamini-Hochberg procedure.
+fn enrichment(
+    labeled: &[(Row, HashSet<Pid>)],
+    threshold: f64,
+) -> Vec<Enrichment> {
+    let is_outlier = |r: &Row| r.values[0].is_some_and(|v| v.abs() > threshold);
+    let total = labeled
+        .iter()
+        .filter(|(r, _)| r.values[0].is_some())
+        .count();
+    let mut total_outliers = 0;
+    let mut outlier_counts: HashMap<&Pid, usize> = HashMap::new();
+    for (_, pids) in labeled.iter().filter(|(r, _)| is_outlier(r)) {
+        total_outliers += 1;
+        for pid in pids {
+            *outlier_counts.entry(pid).or_default() += 1;
+        }
+    }
+
+    let lf = LnFactorials::new(total);
+    let mut ret: Vec<_> = errors_by_param(labeled, 0)
+        .into_iter()
+        .map(|(pid, errs)| {
+            let count = errs.len();
+            let outliers = outlier_counts.get(&pid).copied().unwrap_or(0);
+            Enrichment {
+                mean: errs.mean(),
+                odds_ratio: odds_ratio(
+                    outliers,
+                    count - outliers,
+                    total_outliers - outliers,
+                    total - count - (total_outliers - outliers),
+                ),
+                pvalue: lf.hypergeometric_sf(
+                    outliers,
+                    total,
+                    count,
+                    total_outliers,
+                ),
+                adjusted: 0.0,
+                pid,
+                count,
+                outliers,
+            }
+        })
+        .collect();
+
+    let pvalues: Vec<_> = ret.iter().map(|e| e.pvalue).collect();
+    for (e, adj) in ret.iter_mut().zip(benjamini_hochberg(&pvalues)) {
+        e.adjusted = adj;
+    }
+    ret.sort_by(|a, b| {
+        a.adjusted
+            .total_cmp(&b.adjusted)
+            .then(a.pvalue.total_cmp(&b.pvalue))
+    });
+    ret
+}
+
+pub(crate) fn print_enrichment(
+    labeled: &[(Row, HashSet<Pid>)],
+    threshold: f64,
+) {
+    println!("param,mean,count,outliers,odds_ratio,p,p_adj");
+    for Enrichment {
+        pid,
+        mean,
+        count,
+        outliers,
+        odds_ratio,
+        pvalue,
+        adjusted,
+    } in enrichment(labeled, threshold)
+    {
+        println!(
+            "{pid},{mean:.8},{count},{outliers},{odds_ratio:.4},\
+             {pvalue:.4e},{adjusted:.4e}"
+        );
+    }
+}
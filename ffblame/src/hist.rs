@@ -0,0 +1,249 @@
+//! Write the distributions of the values attributed to each parameter
+
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use fftools::Pid;
+
+use crate::{errors_by_param, metrics::Row, Errors};
+
+#[cfg(test)]
+mod tests;
+
+/// A single histogram bin
+struct Bin {
+    lo: f64,
+    hi: f64,
+    count: usize,
+    /// the sum of the weights of the values in the bin
+    weight: f64,
+}
+
+/// Sort `errs` into `bins` equal-width bins spanning `lo` to `hi`. The last bin
+/// includes `hi`, and values outside of the range are dropped.
+fn histogram(errs: &Errors, lo: f64, hi: f64, bins: usize) -> Vec<Bin> {
+    let width = (hi - lo) / bins as f64;
+    let mut ret: Vec<_> = (0..bins)
+        .map(|i| Bin {
+            lo: lo + i as f64 * width,
+            hi: lo + (i + 1) as f64 * width,
+            count: 0,
+            weight: 0.0,
+        })
+        .collect();
+    for (&v, &w) in errs.values.iter().zip(&errs.weights) {
+        if !(lo..=hi).contains(&v) {
+            continue;
+        }
+        let i = (((v - lo) / width) as usize).min(bins - 1);
+        ret[i].count += 1;
+        ret[i].weight += w;
+    }
+    ret
+}
+
+/// The points of the weighted empirical CDF of `errs`
+fn ecdf(errs: &Errors) -> Vec<(f64, f64)> {
+    let mut pairs: Vec<_> = errs.values.iter().zip(&errs.weights).collect();
+    pairs.sort_by(|a, b| a.0.total_cmp(b.0));
+    let total: f64 = errs.weights.iter().sum();
+    let mut acc = 0.0;
+    pairs
+        .into_iter()
+        .map(|(&v, &w)| {
+            acc += w;
+            (v, acc / total)
+        })
+        .collect()
+}
+
+/// The errors for every parameter for each metric in `names`, with the
+/// parameters sorted by ID
+fn by_metric<'a>(
+    names: &'a [String],
+    labeled: &[(Row, HashSet<Pid>)],
+) -> Vec<(&'a str, Vec<(Pid, Errors)>)> {
+    names
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            let mut errors: Vec<_> =
+                errors_by_param(labeled, i).into_iter().collect();
+            errors.sort_by(|a, b| a.0.cmp(&b.0));
+            (name.as_str(), errors)
+        })
+        .collect()
+}
+
+/// The range of the histograms for a metric: `range` if provided, otherwise
+/// the full range of values attributed to any parameter, widened by 0.5 on
+/// each side if all of the values are equal. `None` if there is no range given
+/// and the metric has no values.
+fn metric_range(
+    errors: &[(Pid, Errors)],
+    range: Option<(f64, f64)>,
+) -> Option<(f64, f64)> {
+    if range.is_some() {
+        return range;
+    }
+    let (lo, hi) = errors
+        .iter()
+        .flat_map(|(_, errs)| &errs.values)
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), &v| {
+            (lo.min(v), hi.max(v))
+        });
+    match lo.partial_cmp(&hi)? {
+        std::cmp::Ordering::Less => Some((lo, hi)),
+        std::cmp::Ordering::Equal => Some((lo - 0.5, hi + 0.5)),
+        std::cmp::Ordering::Greater => None,
+    }
+}
+
+/// Write the histograms of every parameter for every metric to `path` in long
+/// format, with one line per bin. If `svg_dir` is provided, also render each
+/// histogram as an SVG file named `<metric>_<param>.svg` in that directory.
+pub(crate) fn write_histograms(
+    path: impl AsRef<Path>,
+    names: &[String],
+    labeled: &[(Row, HashSet<Pid>)],
+    bins: usize,
+    range: Option<(f64, f64)>,
+    svg_dir: Option<&Path>,
+) -> io::Result<()> {
+    let mut w = BufWriter::new(File::create(path)?);
+    writeln!(w, "metric,param,bin_lo,bin_hi,count,weight")?;
+    for (name, errors) in by_metric(names, labeled) {
+        let Some((lo, hi)) = metric_range(&errors, range) else {
+            continue;
+        };
+        for (pid, errs) in &errors {
+            let hist = histogram(errs, lo, hi, bins);
+            for Bin {
+                lo,
+                hi,
+                count,
+                weight,
+            } in &hist
+            {
+                writeln!(
+                    w,
+                    "{name},{pid},{lo:.8},{hi:.8},{count},{weight:.8}"
+                )?;
+            }
+            if let Some(dir) = svg_dir {
+                let file = dir.join(svg_file_name(name, pid));
+                std::fs::write(file, render_svg(name, pid, &hist))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Write the empirical CDF of every parameter for every metric to `path` in
+/// long format, with one line per value
+pub(crate) fn write_ecdfs(
+    path: impl AsRef<Path>,
+    names: &[String],
+    labeled: &[(Row, HashSet<Pid>)],
+) -> io::Result<()> {
+    let mut w = BufWriter::new(File::create(path)?);
+    writeln!(w, "metric,param,value,cdf")?;
+    for (name, errors) in by_metric(names, labeled) {
+        for (pid, errs) in &errors {
+            for (v, p) in ecdf(errs) {
+                writeln!(w, "{name},{pid},{v:.8},{p:.8}")?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The name of the SVG file for the histogram of `pid` for `metric`, with any
+/// characters other than ASCII letters, digits, `-`, `_`, and `.` replaced by
+/// `_` so that names from CSV headers can't escape the output directory
+fn svg_file_name(metric: &str, pid: &str) -> String {
+    format!("{metric}_{pid}.svg")
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || "-_.".contains(c) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Escape the characters of `s` that are special in XML text
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Render `hist` as a simple SVG bar chart of the counts in each bin
+fn render_svg(metric: &str, pid: &str, hist: &[Bin]) -> String {
+    const WIDTH: f64 = 640.0;
+    const HEIGHT: f64 = 400.0;
+    const MARGIN: f64 = 50.0;
+
+    let plot_w = WIDTH - 2.0 * MARGIN;
+    let plot_h = HEIGHT - 2.0 * MARGIN;
+    let max = hist.iter().map(|b| b.count).max().unwrap_or(0).max(1) as f64;
+    let bar_w = plot_w / hist.len() as f64;
+    let (metric, pid) = (escape(metric), escape(pid));
+
+    let mut s = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" \
+         width=\"{WIDTH}\" height=\"{HEIGHT}\" font-family=\"sans-serif\" \
+         font-size=\"12\">\n"
+    );
+    s.push_str(&format!(
+        "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\" font-size=\"16\">\
+         {pid}</text>\n",
+        WIDTH / 2.0,
+        MARGIN / 2.0,
+    ));
+    for (i, bin) in hist.iter().enumerate() {
+        let h = bin.count as f64 / max * plot_h;
+        s.push_str(&format!(
+            "<rect x=\"{:.2}\" y=\"{:.2}\" width=\"{:.2}\" height=\"{:.2}\" \
+             fill=\"steelblue\" stroke=\"white\"/>\n",
+            MARGIN + i as f64 * bar_w,
+            HEIGHT - MARGIN - h,
+            bar_w,
+            h,
+        ));
+    }
+    // axes
+    s.push_str(&format!(
+        "<path d=\"M{m} {t} V{b} H{r}\" fill=\"none\" stroke=\"black\"/>\n",
+        m = MARGIN,
+        t = MARGIN,
+        b = HEIGHT - MARGIN,
+        r = WIDTH - MARGIN,
+    ));
+    if let (Some(first), Some(last)) = (hist.first(), hist.last()) {
+        let y = HEIGHT - MARGIN + 16.0;
+        for (x, label) in [(MARGIN, first.lo), (WIDTH - MARGIN, last.hi)] {
+            s.push_str(&format!(
+                "<text x=\"{x}\" y=\"{y}\" text-anchor=\"middle\">\
+                 {label:.2}</text>\n"
+            ));
+        }
+    }
+    s.push_str(&format!(
+        "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">{metric}</text>\n\
+         <text x=\"{}\" y=\"{}\" text-anchor=\"end\">{max}</text>\n",
+        WIDTH / 2.0,
+        HEIGHT - 10.0,
+        MARGIN - 4.0,
+        MARGIN + 4.0,
+    ));
+    s.push_str("</svg>\n");
+    s
+}
@@ -0,0 +1,59 @@
+use super::*;
+
+fn errors(values: &[f64], weights: &[f64]) -> Errors {
+    Errors {
+        values: values.to_vec(),
+        weights: weights.to_vec(),
+    }
+}
+
+#[test]
+fn test_histogram() {
+    let errs = errors(
+        &[0.0, 0.5, 1.0, 2.0, 2.0, 3.0],
+        &[1.0, 2.0, 1.0, 1.0, 0.5, 1.0],
+    );
+    let hist = histogram(&errs, 0.0, 2.0, 4);
+    let bounds: Vec<_> = hist.iter().map(|b| (b.lo, b.hi)).collect();
+    assert_eq!(bounds, [(0.0, 0.5), (0.5, 1.0), (1.0, 1.5), (1.5, 2.0)]);
+    // 3.0 is out of range, and the upper bound 2.0 falls in the last bin
+    let counts: Vec<_> = hist.iter().map(|b| (b.count, b.weight)).collect();
+    assert_eq!(counts, [(1, 1.0), (1, 2.0), (1, 1.0), (2, 1.5)]);
+}
+
+#[test]
+fn test_metric_range() {
+    let errs = |values: &[f64]| {
+        vec![("t1".to_owned(), errors(values, &vec![1.0; values.len()]))]
+    };
+    assert_eq!(
+        metric_range(&errs(&[1.0, -2.0, 0.5]), None),
+        Some((-2.0, 1.0))
+    );
+    assert_eq!(metric_range(&errs(&[1.0, 1.0]), None), Some((0.5, 1.5)));
+    assert_eq!(metric_range(&errs(&[]), None), None);
+    assert_eq!(metric_range(&errs(&[]), Some((0.0, 2.0))), Some((0.0, 2.0)));
+
+    // every value lands in a finite bin when they're all equal
+    let (lo, hi) = metric_range(&errs(&[1.0, 1.0]), None).unwrap();
+    let hist = histogram(&errs(&[1.0, 1.0])[0].1, lo, hi, 4);
+    assert!(hist.iter().all(|b| b.lo.is_finite() && b.hi.is_finite()));
+    assert_eq!(hist.iter().map(|b| b.count).sum::<usize>(), 2);
+}
+
+#[test]
+fn test_ecdf() {
+    let errs = errors(&[2.0, -1.0, 0.5], &[1.0, 2.0, 1.0]);
+    assert_eq!(ecdf(&errs), [(-1.0, 0.5), (0.5, 0.75), (2.0, 1.0)]);
+}
+
+#[test]
+fn test_svg() {
+    assert_eq!(svg_file_name("dde", "t17a"), "dde_t17a.svg");
+    assert_eq!(svg_file_name("../a/b", "t1"), ".._a_b_t1.svg");
+
+    let hist = histogram(&errors(&[0.5], &[1.0]), 0.0, 1.0, 2);
+    let svg = render_svg("a<b & c>", "t1", &hist);
+    assert!(svg.contains(">a&lt;b &amp; c&gt;</text>"));
+    assert!(!svg.contains("a<b"));
+}
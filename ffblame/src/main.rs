@@ -1,7 +1,8 @@
 //! read ib output CSV files and assign errors to parameters
 
+use clap::Parser;
 use fftools::{
-    die, load_csv, load_dataset, parameter_map::ParameterMap, Record,
+    die, load_dataset, parameter_map::ParameterMap, stats::weighted_mean, Pid,
 };
 use log::debug;
 use openff_toolkit::ForceField;
@@ -9,73 +10,310 @@ use rayon::prelude::*;
 use rdkit_rs::ROMol;
 use std::{
     collections::{HashMap, HashSet},
-    iter,
+    path::PathBuf,
 };
 
-fn mean(v: &[f64]) -> f64 {
-    v.iter().sum::<f64>() / v.len() as f64
+use crate::aggregate::{aggregate, Aggregate};
+use crate::metrics::{load_metrics, take_weights, Row};
+
+mod aggregate;
+mod enrichment;
+mod hist;
+mod metrics;
+mod trend;
+
+#[derive(Parser)]
+#[command(version, about, long_about = None)]
+struct Cli {
+    /// CSV of QCArchive record ID, value pairs. More than one value column
+    /// yields one metric per column
+    records: PathBuf,
+
+    /// The dataset JSON file the records came from
+    dataset: PathBuf,
+
+    /// The force field to label the dataset with
+    forcefield: String,
+
+    /// Additional metric CSV files to join with `records` by record ID. The
+    /// dataset is only labeled once, and the output has a count and mean
+    /// column for every metric
+    #[arg(short, long)]
+    metric: Vec<PathBuf>,
+
+    /// Drop records missing from any metric instead of computing each metric
+    /// over the records it has values for
+    #[arg(short, long, default_value_t = false)]
+    complete: bool,
+
+    /// Instead of the plain mean table, rank parameters by their enrichment
+    /// among the records whose absolute value of the first metric exceeds this
+    /// threshold
+    #[arg(short, long)]
+    enrichment: Option<f64>,
+
+    /// Combine the values of all of the records for each unique molecule
+    /// before attributing them to parameters, so that molecules with many
+    /// conformers don't dominate the statistics
+    #[arg(short, long, value_enum)]
+    aggregate: Option<Aggregate>,
+
+    /// Use the metric with this name as per-record weights instead of
+    /// reporting it
+    #[arg(short, long)]
+    weights: Option<String>,
+
+    /// Write binned histograms of the values attributed to each parameter for
+    /// every metric to this file, in long format
+    #[arg(long)]
+    histogram: Option<PathBuf>,
+
+    /// The number of histogram bins
+    #[arg(long, default_value_t = 20)]
+    bins: usize,
+
+    /// The lower and upper bounds of the histogram bins. Defaults to the full
+    /// range of each metric
+    #[arg(long, num_args = 2, value_names = ["LO", "HI"])]
+    range: Option<Vec<f64>>,
+
+    /// Also render each histogram as an SVG file in this directory
+    #[arg(long, requires = "histogram")]
+    svg: Option<PathBuf>,
+
+    /// Write the empirical CDF of the values attributed to each parameter for
+    /// every metric to this file, in long format
+    #[arg(long)]
+    ecdf: Option<PathBuf>,
+
+    /// Track the per-parameter statistics across a sequence of force fields.
+    /// The file lists one `forcefield csv` pair per line, which are evaluated
+    /// in order after the `forcefield` and `records` given on the command
+    /// line. Parameters are aligned across iterations by SMIRKS
+    #[arg(
+        long,
+        conflicts_with_all = ["metric", "enrichment", "histogram", "ecdf"]
+    )]
+    trend: Option<PathBuf>,
+
+    /// Flag a parameter as regressed in a trend when the magnitude of its mean
+    /// grows by more than this amount from one iteration to the next
+    #[arg(long, default_value_t = 0.0, requires = "trend")]
+    tolerance: f64,
 }
 
+/// Label each [Row] with the set of parameter IDs assigned to its molecule.
+///
 /// TODO there is a question here of whether or not to consider only unique
 /// values. it might actually make more sense to count it as an additional error
 /// for each occurrence of the parameter in a molecule. this only counts once
 /// per record
 fn process_records(
-    records: Vec<Record>,
-    dataset: HashMap<String, String>,
-    params: ParameterMap,
-) -> Vec<(String, f64)> {
-    let map_op = |r: Record| -> Vec<(String, f64)> {
+    records: Vec<Row>,
+    dataset: &HashMap<String, String>,
+    params: &ParameterMap,
+) -> Vec<(Row, HashSet<Pid>)> {
+    let map_op = |r: Row| -> (Row, HashSet<Pid>) {
         let smiles = dataset.get(&r.id.to_string()).unwrap();
         let mut mol = ROMol::from_smiles(smiles);
         mol.openff_clean();
-        let pids: HashSet<_> =
-            params.label_molecule(&mol).into_values().collect();
-        // Collect could possibly be replaced with par_bridge
-        pids.into_iter().zip(iter::repeat(r.value)).collect()
+        (r, params.label_molecule(&mol).into_values().collect())
     };
-    records.into_par_iter().flat_map(map_op).collect()
+    records.into_par_iter().map(map_op).collect()
 }
 
-fn main() {
-    env_logger::init();
+/// The values and weights of the records labeled with a single parameter
+#[derive(Default)]
+struct Errors {
+    values: Vec<f64>,
+    weights: Vec<f64>,
+}
 
-    let args: Vec<_> = std::env::args().collect();
-    if args.len() < 4 {
-        die!("Usage: ffblame <data.csv> <dataset.json> <forcefield.offxml>");
+impl Errors {
+    fn len(&self) -> usize {
+        self.values.len()
     }
-    debug!("loading CSV from {}", &args[1]);
-    let records = load_csv(&args[1])
-        .unwrap_or_else(|e| die!("failed to load {} with {}", args[1], e));
-    debug!("loading dataset from {}", &args[2]);
-    let dataset = load_dataset(&args[2])
-        .unwrap_or_else(|e| die!("failed to load {} with {}", args[2], e));
-    debug!("loading forcefield from {}", &args[3]);
-    let forcefield = ForceField::load(&args[3])
-        .unwrap_or_else(|e| die!("failed to load {} with {}", args[3], e));
+
+    fn mean(&self) -> f64 {
+        weighted_mean(&self.values, &self.weights)
+    }
+}
+
+/// Collect the values of `metric` for every record labeled with each parameter,
+/// skipping records with no value for `metric`
+fn errors_by_param(
+    labeled: &[(Row, HashSet<Pid>)],
+    metric: usize,
+) -> HashMap<Pid, Errors> {
+    let mut errors: HashMap<Pid, Errors> = HashMap::new();
+    for (row, pids) in labeled {
+        let Some(value) = row.values[metric] else {
+            continue;
+        };
+        for pid in pids {
+            let errs = errors.entry(pid.clone()).or_default();
+            errs.values.push(value);
+            errs.weights.push(row.weight);
+        }
+    }
+    errors
+}
+
+/// Print one row per parameter with the number of records and mean value for
+/// each metric in `names`
+fn print_wide(names: &[String], labeled: &[(Row, HashSet<Pid>)]) {
+    let by_metric: Vec<_> = (0..names.len())
+        .map(|i| errors_by_param(labeled, i))
+        .collect();
+    let mut pids: Vec<_> = by_metric.iter().flat_map(|m| m.keys()).collect();
+    pids.sort();
+    pids.dedup();
+
+    print!("param");
+    for name in names {
+        print!(",{name}_count,{name}_mean");
+    }
+    println!();
+    for pid in pids {
+        print!("{pid}");
+        for errors in &by_metric {
+            match errors.get(pid) {
+                Some(errs) => print!(",{},{:.8}", errs.len(), errs.mean()),
+                None => print!(",0,NA"),
+            }
+        }
+        println!();
+    }
+}
+
+/// Load the parameters to label molecules with from `forcefield`
+fn load_params(forcefield: &str) -> ParameterMap {
+    debug!("loading forcefield from {forcefield}");
+    let forcefield = ForceField::load(forcefield)
+        .unwrap_or_else(|e| die!("failed to load {} with {}", forcefield, e));
     debug!("building parameter smirks");
-    let params: ParameterMap = forcefield
-        .get_parameter_handler("ProperTorsions")
-        .unwrap()
-        .into();
+    ParameterMap::from_handler(&forcefield, "ProperTorsions").unwrap()
+}
 
+/// Load and join the metric CSVs in `paths`, then apply the weight and
+/// aggregation options from `args`
+fn load_rows(
+    args: &Cli,
+    paths: &[PathBuf],
+    dataset: &HashMap<String, String>,
+) -> (Vec<String>, Vec<Row>) {
+    debug!("loading CSVs from {paths:?}");
+    let (mut names, mut records) = load_metrics(paths, args.complete);
+    if let Some(weights) = &args.weights {
+        take_weights(&mut names, &mut records, weights);
+    }
     debug!(
-        "loaded {} records, {} in dataset, {} proper torsions",
+        "loaded {} records with {} metrics",
         records.len(),
-        dataset.len(),
-        params.len(),
+        names.len()
     );
 
+    if let Some(how) = args.aggregate {
+        records = aggregate(records, dataset, how);
+        debug!("aggregated records into {} molecules", records.len());
+    }
+    (names, records)
+}
+
+fn main() {
+    env_logger::init();
+
+    let args = Cli::parse();
+
+    debug!("loading dataset from {:?}", args.dataset);
+    let dataset = load_dataset(&args.dataset).unwrap_or_else(|e| {
+        die!("failed to load {:?} with {}", args.dataset, e)
+    });
+    debug!("loaded {} records from dataset", dataset.len());
+
+    if let Some(path) = &args.trend {
+        let rest = trend::load_iterations(path)
+            .unwrap_or_else(|e| die!("failed to load {:?} with {}", path, e));
+        let mut iterations = Vec::new();
+        for (forcefield, csv) in
+            std::iter::once((args.forcefield.clone(), args.records.clone()))
+                .chain(rest)
+        {
+            let params = load_params(&forcefield);
+            let smirks = trend::smirks_map(
+                params
+                    .smirks()
+                    .map(|(pid, smirks)| (pid.clone(), smirks.to_owned())),
+            )
+            .unwrap_or_else(|e| die!("in {forcefield}: {e}"));
+            let (names, records) = load_rows(&args, &[csv], &dataset);
+            debug!("processing records for {forcefield}");
+            let labeled = process_records(records, &dataset, &params);
+            iterations.push(trend::Iteration::new(
+                forcefield, names, &labeled, &smirks,
+            ));
+        }
+        trend::write_trend(
+            &mut std::io::stdout().lock(),
+            &iterations,
+            args.tolerance,
+        )
+        .unwrap_or_else(|e| die!("failed to write trend with {e}"));
+        return;
+    }
+
+    let paths: Vec<_> = std::iter::once(args.records.clone())
+        .chain(args.metric.iter().cloned())
+        .collect();
+    let (names, records) = load_rows(&args, &paths, &dataset);
+    let params = load_params(&args.forcefield);
+    debug!("loaded {} proper torsions", params.len());
+
     debug!("processing records");
-    let res = process_records(records, dataset, params);
+    let labeled = process_records(records, &dataset, &params);
+    if let Some(path) = &args.histogram {
+        if args.bins == 0 {
+            die!("--bins must be positive");
+        }
+        let range = args.range.as_deref().map(|r| (r[0], r[1]));
+        if let Some((lo, hi)) = range {
+            if lo >= hi {
+                die!("--range LO must be less than HI, got {lo} {hi}");
+            }
+        }
+        if let Some(dir) = &args.svg {
+            std::fs::create_dir_all(dir).unwrap_or_else(|e| {
+                die!("failed to create {:?} with {}", dir, e)
+            });
+        }
+        hist::write_histograms(
+            path,
+            &names,
+            &labeled,
+            args.bins,
+            range,
+            args.svg.as_deref(),
+        )
+        .unwrap_or_else(|e| die!("failed to write {:?} with {}", path, e));
+    }
+
+    if let Some(path) = &args.ecdf {
+        hist::write_ecdfs(path, &names, &labeled)
+            .unwrap_or_else(|e| die!("failed to write {:?} with {}", path, e));
+    }
+
+    if let Some(threshold) = args.enrichment {
+        enrichment::print_enrichment(&labeled, threshold);
+        return;
+    }
 
-    let mut errors: HashMap<String, Vec<f64>> = HashMap::new();
-    for (pid, val) in res {
-        errors.entry(pid).or_default().push(val);
+    if names.len() > 1 {
+        print_wide(&names, &labeled);
+        return;
     }
 
     println!("param,mean");
-    for (pid, errs) in errors {
-        println!("{pid},{:.8}", mean(&errs));
+    for (pid, errs) in errors_by_param(&labeled, 0) {
+        println!("{pid},{:.8}", errs.mean());
     }
 }
@@ -0,0 +1,134 @@
+//! Load and join several metric CSV files on their record IDs
+
+use std::{collections::BTreeMap, path::PathBuf};
+
+use fftools::{die, load_table, Table};
+use log::warn;
+
+#[cfg(test)]
+mod tests;
+
+/// A record joined across every metric file by its QCArchive record ID
+#[derive(Debug)]
+pub(crate) struct Row {
+    pub(crate) id: usize,
+
+    /// one value per metric, `None` where that metric has no entry for the
+    /// record
+    pub(crate) values: Vec<Option<f64>>,
+
+    /// the weight of this record in the per-parameter statistics
+    pub(crate) weight: f64,
+}
+
+/// Join `tables`, each paired with the name to give its value column if it
+/// only has one, on their record IDs. Tables with a single value column
+/// contribute one metric with that name, while tables with several value
+/// columns contribute one metric per column, named by the header.
+fn join(
+    tables: Vec<(String, Table)>,
+) -> Result<(Vec<String>, Vec<Row>), String> {
+    let mut names: Vec<String> = Vec::new();
+    let mut joined: BTreeMap<usize, Vec<Option<f64>>> = BTreeMap::new();
+    for (name, table) in tables {
+        let offset = names.len();
+        match table.columns.len() {
+            0 => return Err(format!("{name} has no value columns")),
+            1 => names.push(name.clone()),
+            _ => names.extend(table.columns),
+        }
+        for (id, values) in table.rows {
+            let row = joined.entry(id).or_default();
+            if row.len() > offset {
+                return Err(format!("duplicate record {id} in {name}"));
+            }
+            row.resize(offset, None);
+            row.extend(values);
+        }
+    }
+
+    for (i, name) in names.iter().enumerate() {
+        if names[..i].contains(name) {
+            return Err(format!("duplicate metric name {name}"));
+        }
+    }
+
+    let rows = joined
+        .into_iter()
+        .map(|(id, mut values)| {
+            values.resize(names.len(), None);
+            Row {
+                id,
+                values,
+                weight: 1.0,
+            }
+        })
+        .collect();
+    Ok((names, rows))
+}
+
+/// Load each of the CSV files in `paths` and [join] them on their record IDs,
+/// naming single-column files after their file stems.
+///
+/// If `complete` is true, records missing a value for any metric are dropped.
+/// Otherwise they are kept with `None` for the missing metrics, and the number
+/// of records missing from each metric is reported as a warning.
+pub(crate) fn load_metrics(
+    paths: &[PathBuf],
+    complete: bool,
+) -> (Vec<String>, Vec<Row>) {
+    let tables = paths
+        .iter()
+        .map(|path| {
+            let table = load_table(path).unwrap_or_else(|e| {
+                die!("failed to load {:?} with {}", path, e)
+            });
+            let stem = path.file_stem().unwrap_or_default();
+            (stem.to_string_lossy().into_owned(), table)
+        })
+        .collect();
+    let (names, mut rows) =
+        join(tables).unwrap_or_else(|e| die!("failed to join metrics: {e}"));
+
+    let before = rows.len();
+    if complete {
+        rows.retain(|r| r.values.iter().all(Option::is_some));
+        if rows.len() < before {
+            warn!("dropped {} incomplete records", before - rows.len());
+        }
+    } else {
+        for (i, name) in names.iter().enumerate() {
+            let missing = rows.iter().filter(|r| r.values[i].is_none()).count();
+            if missing > 0 {
+                warn!("{missing} of {before} records have no value for {name}");
+            }
+        }
+    }
+
+    (names, rows)
+}
+
+/// Remove the metric called `name` from `names` and `rows` and use its values
+/// as the weights of `rows` instead. Records without a weight are dropped with
+/// a warning.
+pub(crate) fn take_weights(
+    names: &mut Vec<String>,
+    rows: &mut Vec<Row>,
+    name: &str,
+) {
+    let Some(col) = names.iter().position(|n| n == name) else {
+        die!("weight column {name} not found in {names:?}");
+    };
+    names.remove(col);
+    let before = rows.len();
+    rows.retain_mut(|row| match row.values.remove(col) {
+        Some(weight) => {
+            row.weight = weight;
+            true
+        }
+        None => false,
+    });
+    if rows.len() < before {
+        warn!("dropped {} records without a weight", before - rows.len());
+    }
+}
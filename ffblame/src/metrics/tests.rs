@@ -0,0 +1,93 @@
+use super::*;
+
+/// Write each of `files` to a temporary directory unique to `test`, returning
+/// their paths
+fn write_csvs(test: &str, files: &[(&str, &str)]) -> Vec<PathBuf> {
+    let dir = std::env::temp_dir().join(format!("ffblame_{test}"));
+    std::fs::create_dir_all(&dir).unwrap();
+    files
+        .iter()
+        .map(|(name, contents)| {
+            let path = dir.join(name);
+            std::fs::write(&path, contents).unwrap();
+            path
+        })
+        .collect()
+}
+
+#[test]
+fn test_load_metrics() {
+    let paths = write_csvs(
+        "load_metrics",
+        &[
+            ("dde.csv", "id,value\n1,0.5\n2,-1.0\n3,2.0\n"),
+            ("multi.csv", "id,rmsd,tfd\n1,0.1,0.01\n3,0.3,\n4,0.4,0.04\n"),
+        ],
+    );
+
+    let (names, rows) = load_metrics(&paths, false);
+    assert_eq!(names, ["dde", "rmsd", "tfd"]);
+    let got: Vec<_> = rows.iter().map(|r| (r.id, r.values.clone())).collect();
+    assert_eq!(
+        got,
+        [
+            (1, vec![Some(0.5), Some(0.1), Some(0.01)]),
+            (2, vec![Some(-1.0), None, None]),
+            (3, vec![Some(2.0), Some(0.3), None]),
+            (4, vec![None, Some(0.4), Some(0.04)]),
+        ]
+    );
+    assert!(rows.iter().all(|r| r.weight == 1.0));
+
+    let (_, rows) = load_metrics(&paths, true);
+    let ids: Vec<_> = rows.iter().map(|r| r.id).collect();
+    assert_eq!(ids, [1]);
+}
+
+fn table(columns: &[&str], rows: &[(usize, &[Option<f64>])]) -> Table {
+    Table {
+        columns: columns.iter().map(|c| c.to_string()).collect(),
+        rows: rows.iter().map(|(id, vs)| (*id, vs.to_vec())).collect(),
+    }
+}
+
+#[test]
+fn test_join() {
+    let a = table(&["value"], &[(1, &[Some(1.0)]), (2, &[Some(2.0)])]);
+    let b = table(&["x", "y"], &[(2, &[Some(3.0), Some(4.0)])]);
+    let (names, rows) =
+        join(vec![("a".to_owned(), a), ("b".to_owned(), b)]).unwrap();
+    assert_eq!(names, ["a", "x", "y"]);
+    assert_eq!(rows[0].values, [Some(1.0), None, None]);
+    assert_eq!(rows[1].values, [Some(2.0), Some(3.0), Some(4.0)]);
+
+    let dup = table(&["value"], &[(1, &[Some(1.0)]), (1, &[Some(2.0)])]);
+    let err = join(vec![("dup".to_owned(), dup)]).unwrap_err();
+    assert_eq!(err, "duplicate record 1 in dup");
+
+    let ids = table(&[], &[(1, &[])]);
+    let err = join(vec![("ids".to_owned(), ids)]).unwrap_err();
+    assert_eq!(err, "ids has no value columns");
+
+    let a = || ("a".to_owned(), table(&["value"], &[]));
+    let err = join(vec![a(), a()]).unwrap_err();
+    assert_eq!(err, "duplicate metric name a");
+}
+
+#[test]
+fn test_take_weights() {
+    let w = table(
+        &["dde", "w"],
+        &[
+            (1, &[Some(0.5), Some(2.0)]),
+            (2, &[Some(1.0), None]),
+            (3, &[Some(1.5), Some(0.5)]),
+        ],
+    );
+    let (mut names, mut rows) = join(vec![("w".to_owned(), w)]).unwrap();
+    take_weights(&mut names, &mut rows, "w");
+    assert_eq!(names, ["dde"]);
+    let got: Vec<_> = rows.iter().map(|r| (r.id, r.weight)).collect();
+    assert_eq!(got, [(1, 2.0), (3, 0.5)]);
+    assert!(rows.iter().all(|r| r.values.len() == 1));
+}
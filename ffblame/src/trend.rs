@@ -0,0 +1,150 @@
+//! Follow the per-parameter statistics across a sequence of force fields
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs::read_to_string,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use fftools::Pid;
+
+use crate::{errors_by_param, metrics::Row};
+
+#[cfg(test)]
+mod tests;
+
+/// Load an ordered list of force field, CSV pairs from `path`, one
+/// whitespace-separated pair per line. Blank lines and lines starting with `#`
+/// are skipped.
+pub(crate) fn load_iterations(
+    path: impl AsRef<Path>,
+) -> io::Result<Vec<(String, PathBuf)>> {
+    let mut ret = Vec::new();
+    for line in read_to_string(path)?.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let sp: Vec<_> = line.split_ascii_whitespace().collect();
+        let [ff, csv] = sp[..] else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("expected `forcefield csv`, found `{line}`"),
+            ));
+        };
+        ret.push((ff.to_owned(), PathBuf::from(csv)));
+    }
+    Ok(ret)
+}
+
+/// Map each parameter ID in `pairs` to its SMIRKS pattern, returning an error
+/// if two parameters share a pattern, since they couldn't be told apart
+/// across iterations
+pub(crate) fn smirks_map(
+    pairs: impl IntoIterator<Item = (Pid, String)>,
+) -> Result<HashMap<Pid, String>, String> {
+    let mut seen: HashMap<String, Pid> = HashMap::new();
+    let mut ret = HashMap::new();
+    for (pid, smirks) in pairs {
+        if let Some(other) = seen.insert(smirks.clone(), pid.clone()) {
+            return Err(format!("{other} and {pid} share the SMIRKS {smirks}"));
+        }
+        ret.insert(pid, smirks);
+    }
+    Ok(ret)
+}
+
+/// The statistics of one parameter in one iteration
+struct Stats {
+    pid: Pid,
+    count: usize,
+    mean: f64,
+}
+
+/// The per-parameter statistics for a single force field, keyed by metric
+/// index and SMIRKS pattern
+pub(crate) struct Iteration {
+    forcefield: String,
+
+    /// the names of this iteration's metrics
+    names: Vec<String>,
+    stats: HashMap<(usize, String), Stats>,
+}
+
+impl Iteration {
+    /// Summarize `labeled` for every metric in `names`, using `smirks` to key
+    /// each parameter ID by its SMIRKS pattern
+    pub(crate) fn new(
+        forcefield: String,
+        names: Vec<String>,
+        labeled: &[(Row, HashSet<Pid>)],
+        smirks: &HashMap<Pid, String>,
+    ) -> Self {
+        let mut stats = HashMap::new();
+        for metric in 0..names.len() {
+            for (pid, errs) in errors_by_param(labeled, metric) {
+                let key = (metric, smirks[&pid].clone());
+                let s = Stats {
+                    count: errs.len(),
+                    mean: errs.mean(),
+                    pid,
+                };
+                stats.insert(key, s);
+            }
+        }
+        Self {
+            forcefield,
+            names,
+            stats,
+        }
+    }
+}
+
+/// Write the statistics of every parameter in every iteration to `w` in long
+/// format. Parameters are aligned across iterations by SMIRKS pattern and
+/// metrics by their position, each labeled with its name in its own
+/// iteration. `change` is the difference
+/// from the mean for the same SMIRKS in the previous iteration, and a parameter
+/// is flagged as regressed when the magnitude of its mean grew by more than
+/// `tolerance`.
+pub(crate) fn write_trend(
+    w: &mut impl Write,
+    iterations: &[Iteration],
+    tolerance: f64,
+) -> io::Result<()> {
+    let mut keys: Vec<_> = iterations
+        .iter()
+        .flat_map(|it| it.stats.keys())
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    keys.sort();
+
+    writeln!(
+        w,
+        "iteration,forcefield,metric,smirks,param,count,mean,change,regressed"
+    )?;
+    for key @ (metric, smirks) in keys {
+        let mut prev: Option<f64> = None;
+        for (i, it) in iterations.iter().enumerate() {
+            let Some(Stats { pid, count, mean }) = it.stats.get(key) else {
+                prev = None;
+                continue;
+            };
+            let (change, regressed) = match prev {
+                Some(p) => (
+                    format!("{:.8}", mean - p),
+                    mean.abs() - p.abs() > tolerance,
+                ),
+                None => ("NA".to_owned(), false),
+            };
+            writeln!(
+                w,
+                "{i},{},{},\"{smirks}\",{pid},{count},{mean:.8},{change},{}",
+                it.forcefield, it.names[*metric], regressed as u8,
+            )?;
+            prev = Some(*mean);
+        }
+    }
+    Ok(())
+}
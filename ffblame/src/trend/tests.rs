@@ -0,0 +1,76 @@
+use super::*;
+
+fn labeled(rows: &[(usize, f64, &[&str])]) -> Vec<(Row, HashSet<Pid>)> {
+    rows.iter()
+        .map(|&(id, value, pids)| {
+            let row = Row {
+                id,
+                values: vec![Some(value)],
+                weight: 1.0,
+            };
+            (row, pids.iter().map(|p| p.to_string()).collect())
+        })
+        .collect()
+}
+
+fn smirks(pairs: &[(&str, &str)]) -> Result<HashMap<Pid, String>, String> {
+    smirks_map(pairs.iter().map(|(p, s)| (p.to_string(), s.to_string())))
+}
+
+#[test]
+fn test_smirks_map() {
+    let got = smirks(&[("t1", "S1"), ("t2", "S2")]).unwrap();
+    assert_eq!(got["t2"], "S2");
+    let err = smirks(&[("t1", "S1"), ("t2", "S1")]).unwrap_err();
+    assert_eq!(err, "t1 and t2 share the SMIRKS S1");
+}
+
+#[test]
+fn test_trend() {
+    // t2 is renamed to t2b in the second iteration, and each iteration's
+    // single-column CSV gives its metric a different name
+    let first = Iteration::new(
+        "a".to_owned(),
+        vec!["dde".to_owned()],
+        &labeled(&[(1, 1.0, &["t1"]), (2, 3.0, &["t1", "t2"])]),
+        &smirks(&[("t1", "S1"), ("t2", "S2")]).unwrap(),
+    );
+    let second = Iteration::new(
+        "b".to_owned(),
+        vec!["value".to_owned()],
+        &labeled(&[(1, 0.5, &["t1"]), (2, 4.0, &["t2b"])]),
+        &smirks(&[("t1", "S1"), ("t2b", "S2")]).unwrap(),
+    );
+
+    let mut out = Vec::new();
+    write_trend(&mut out, &[first, second], 0.5).unwrap();
+    let want = "\
+iteration,forcefield,metric,smirks,param,count,mean,change,regressed
+0,a,dde,\"S1\",t1,2,2.00000000,NA,0
+1,b,value,\"S1\",t1,1,0.50000000,-1.50000000,0
+0,a,dde,\"S2\",t2,1,3.00000000,NA,0
+1,b,value,\"S2\",t2b,1,4.00000000,1.00000000,1
+";
+    assert_eq!(String::from_utf8(out).unwrap(), want);
+}
+
+#[test]
+fn test_load_iterations() {
+    let path = std::env::temp_dir().join("ffblame_test_load_iterations");
+    std::fs::write(
+        &path,
+        "# the next two\nff1.offxml a.csv\n\nff2.offxml b.csv\n",
+    )
+    .unwrap();
+    let got = load_iterations(&path).unwrap();
+    assert_eq!(
+        got,
+        [
+            ("ff1.offxml".to_owned(), PathBuf::from("a.csv")),
+            ("ff2.offxml".to_owned(), PathBuf::from("b.csv")),
+        ]
+    );
+
+    std::fs::write(&path, "ff1.offxml\n").unwrap();
+    assert!(load_iterations(&path).is_err());
+}
@@ -0,0 +1,140 @@
+//! Test structural features for over-representation among improved or
+//! degraded records
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+use std::io::{self, Write};
+
+use fftools::stats::{benjamini_hochberg, odds_ratio, LnFactorials};
+
+#[cfg(test)]
+mod tests;
+
+/// A structural feature a molecule can contain
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub(crate) enum Feature {
+    /// a functional group from [crate::groups::GROUPS]
+    Group(&'static str),
+    /// the SMILES of a Morgan atom environment
+    Morgan(String),
+}
+
+impl Display for Feature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Feature::Group(name) => write!(f, "group:{name}"),
+            Feature::Morgan(smiles) => write!(f, "morgan:{smiles}"),
+        }
+    }
+}
+
+/// One row of the enrichment table
+#[derive(Debug)]
+pub(crate) struct Enrichment<'a> {
+    pub(crate) feature: &'a Feature,
+    /// number of records containing the feature
+    pub(crate) count: usize,
+    /// number of records in the set containing the feature
+    pub(crate) hits: usize,
+    pub(crate) odds_ratio: f64,
+    pub(crate) pvalue: f64,
+    pub(crate) adjusted: f64,
+    /// unique SMILES of records in the set containing the feature
+    pub(crate) examples: Vec<&'a str>,
+}
+
+/// Compare the fraction of the records in `set`, given as indices into
+/// `records`, containing each feature to the fraction of all `records`
+/// containing it. Features found in fewer than `min_count` records are skipped.
+/// Each feature gets a one-sided Fisher exact test, and the resulting p-values
+/// are corrected for multiple testing with the Benjamini-Hochberg procedure.
+/// Up to `examples` SMILES are collected for each feature, in the order of
+/// `set`.
+pub(crate) fn enrichment<'a>(
+    records: &'a [(&'a str, HashSet<Feature>)],
+    set: &[usize],
+    min_count: usize,
+    examples: usize,
+) -> Vec<Enrichment<'a>> {
+    let total = records.len();
+    let mut counts: HashMap<&Feature, usize> = HashMap::new();
+    for (_, features) in records {
+        for f in features {
+            *counts.entry(f).or_default() += 1;
+        }
+    }
+    let mut hits: HashMap<&Feature, (usize, Vec<&str>)> = HashMap::new();
+    for &i in set {
+        let (smiles, features) = &records[i];
+        for f in features {
+            let (n, ex) = hits.entry(f).or_default();
+            *n += 1;
+            if ex.len() < examples && !ex.contains(smiles) {
+                ex.push(smiles);
+            }
+        }
+    }
+
+    let lf = LnFactorials::new(total);
+    let draws = set.len();
+    let mut ret: Vec<_> = counts
+        .into_iter()
+        .filter(|(_, count)| *count >= min_count)
+        .map(|(feature, count)| {
+            let (k, examples) = hits.remove(feature).unwrap_or_default();
+            Enrichment {
+                feature,
+                count,
+                hits: k,
+                odds_ratio: odds_ratio(
+                    k,
+                    count - k,
+                    draws - k,
+                    total - count - (draws - k),
+                ),
+                pvalue: lf.hypergeometric_sf(k, total, count, draws),
+                adjusted: 0.0,
+                examples,
+            }
+        })
+        .collect();
+
+    let pvalues: Vec<_> = ret.iter().map(|e| e.pvalue).collect();
+    for (e, adj) in ret.iter_mut().zip(benjamini_hochberg(&pvalues)) {
+        e.adjusted = adj;
+    }
+    ret.sort_by(|a, b| {
+        a.adjusted
+            .total_cmp(&b.adjusted)
+            .then(b.odds_ratio.total_cmp(&a.odds_ratio))
+            .then(a.feature.cmp(b.feature))
+    });
+    ret
+}
+
+/// Write `enrichments` for the set called `name` to `w` as CSV rows, with the
+/// example SMILES separated by spaces
+pub(crate) fn write_enrichment(
+    w: &mut impl Write,
+    name: &str,
+    enrichments: &[Enrichment],
+) -> io::Result<()> {
+    for Enrichment {
+        feature,
+        count,
+        hits,
+        odds_ratio,
+        pvalue,
+        adjusted,
+        examples,
+    } in enrichments
+    {
+        writeln!(
+            w,
+            "{name},{feature},{count},{hits},{odds_ratio:.4},{pvalue:.4e},\
+             {adjusted:.4e},{}",
+            examples.join(" ")
+        )?;
+    }
+    Ok(())
+}
@@ -0,0 +1,45 @@
+use super::*;
+
+#[test]
+fn test_enrichment() {
+    let amide = Feature::Group("amide");
+    let env = Feature::Morgan("CC".to_owned());
+    let features = |fs: &[&Feature]| fs.iter().copied().cloned().collect();
+    // the amide is in all four degraded records but only one of the other six,
+    // while the environment is spread evenly
+    let records: Vec<(&str, HashSet<Feature>)> = vec![
+        ("CC(=O)N", features(&[&amide, &env])),
+        ("CC(=O)N", features(&[&amide])),
+        ("CCC(=O)N", features(&[&amide, &env])),
+        ("CCCC(=O)N", features(&[&amide])),
+        ("CCCCC(=O)N", features(&[&amide, &env])),
+        ("CO", features(&[&env])),
+        ("CCO", features(&[])),
+        ("CCCO", features(&[&env])),
+        ("CCCCO", features(&[])),
+        ("CCCCCO", features(&[&env])),
+    ];
+    let got = enrichment(&records, &[3, 0, 1, 2], 1, 2);
+    assert_eq!(got.len(), 2);
+
+    let e = &got[0];
+    assert_eq!(e.feature, &amide);
+    assert_eq!((e.count, e.hits), (5, 4));
+    // 4 of the 5 amides drawn in 4 draws from 10: C(5, 4) / C(10, 4)
+    assert!((e.pvalue - 5.0 / 210.0).abs() < 1e-12);
+    assert_eq!(e.examples, ["CCCC(=O)N", "CC(=O)N"]);
+
+    let e = &got[1];
+    assert_eq!(e.feature, &env);
+    assert_eq!((e.count, e.hits), (6, 2));
+    assert!(e.adjusted > 0.5);
+
+    // rare features are dropped
+    assert_eq!(enrichment(&records, &[0], 6, 2).len(), 1);
+
+    let mut out = Vec::new();
+    write_enrichment(&mut out, "degraded", &got[..1]).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.starts_with("degraded,group:amide,5,4,"));
+    assert!(out.ends_with(",CCCC(=O)N CC(=O)N\n"));
+}
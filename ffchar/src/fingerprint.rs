@@ -0,0 +1,24 @@
+//! Morgan atom environments as structural features
+
+use std::collections::HashSet;
+
+use rdkit_rs::ROMol;
+
+/// The SMILES of the atom environments in the unfolded Morgan fingerprint of
+/// `mol` with radii from 1 up to `radius`. Unlike the bits of a folded
+/// fingerprint, these identify the substructures directly, and environments
+/// with the same structure around different atoms are merged.
+pub(crate) fn morgan_environments(mol: &ROMol, radius: u32) -> HashSet<String> {
+    let mut ret = HashSet::new();
+    for atom in 0..mol.num_atoms() {
+        for r in 1..=radius {
+            let bonds = mol.find_atom_environment_of_radius_n(r, atom);
+            // empty when the molecule doesn't extend r bonds from atom
+            if bonds.is_empty() {
+                break;
+            }
+            ret.insert(mol.path_to_submol(&bonds).to_smiles());
+        }
+    }
+    ret
+}
@@ -0,0 +1,40 @@
+//! A curated library of functional groups to test for enrichment
+
+/// Names and SMARTS patterns of common functional groups, roughly following
+/// the RDKit and Checkmol definitions. The patterns overlap on purpose, so that
+/// both a general group like carbonyl and its more specific forms like amide
+/// can be reported.
+pub(crate) const GROUPS: [(&str, &str); 32] = [
+    ("primary_alcohol", "[CH2X4][OX2H]"),
+    ("secondary_alcohol", "[CHX4]([#6])([#6])[OX2H]"),
+    ("tertiary_alcohol", "[CX4]([#6])([#6])([#6])[OX2H]"),
+    ("phenol", "c[OX2H]"),
+    ("ether", "[OD2]([#6])[#6]"),
+    ("carbonyl", "[CX3]=[OX1]"),
+    ("aldehyde", "[CX3H1](=O)[#6]"),
+    ("ketone", "[#6][CX3](=O)[#6]"),
+    ("carboxylic_acid", "[CX3](=O)[OX2H1]"),
+    ("carboxylate", "[CX3](=O)[OX1-]"),
+    ("ester", "[#6][CX3](=O)[OX2H0][#6]"),
+    ("amide", "[NX3][CX3](=[OX1])[#6]"),
+    ("urea", "[NX3][CX3](=[OX1])[NX3]"),
+    ("carbamate", "[NX3][CX3](=[OX1])[OX2H0]"),
+    ("primary_amine", "[NX3;H2;!$(NC=[O,S,N])][#6]"),
+    ("secondary_amine", "[NX3;H1;!$(NC=[O,S,N])]([#6])[#6]"),
+    ("tertiary_amine", "[NX3;H0;!$(NC=[O,S,N])]([#6])([#6])[#6]"),
+    ("aniline", "c[NX3;!$(NC=[O,S,N])]"),
+    ("ammonium", "[NX4+]"),
+    ("imine", "[CX3]=[NX2]"),
+    ("nitrile", "[NX1]#[CX2]"),
+    ("nitro", "[$([NX3](=O)=O),$([NX3+](=O)[O-])]"),
+    ("sulfonamide", "[SX4](=[OX1])(=[OX1])[NX3]"),
+    ("sulfone", "[#6][SX4](=[OX1])(=[OX1])[#6]"),
+    ("thioether", "[SX2]([#6])[#6]"),
+    ("thiol", "[#16X2H]"),
+    ("halogen", "[F,Cl,Br,I]"),
+    ("trifluoromethyl", "[CX4](F)(F)F"),
+    ("aromatic_nitrogen", "[n]"),
+    ("heteroaromatic_ring", "[a;!c]"),
+    ("alkene", "[CX3]=[CX3]"),
+    ("alkyne", "[CX2]#[CX2]"),
+];
@@ -1,16 +1,91 @@
 //! characterize the improvements and degradations for specific records with the
 //! goal of identifying structural commonalities
 
-use fftools::{
-    die, load_csv, load_dataset, parameter_map::ParameterMap, Record,
-};
+use clap::builder::RangedU64ValueParser;
+use clap::Parser;
+use enrichment::{enrichment, write_enrichment, Feature};
+use fftools::parameter_map::{KeyOrder, MultiParameterMap};
+use fftools::{die, load_csv, load_dataset, Record};
+use groups::GROUPS;
+use log::{info, warn};
 use openff_toolkit::ForceField;
 use rayon::prelude::*;
-use rdkit_rs::ROMol;
-use std::collections::HashMap;
+use rdkit_rs::{find_smarts_matches_mol, ROMol};
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::PathBuf;
 
-fn _mean(v: &[f64]) -> f64 {
-    v.iter().sum::<f64>() / v.len() as f64
+mod enrichment;
+mod fingerprint;
+mod groups;
+mod mcs;
+
+#[cfg(test)]
+mod tests;
+
+#[derive(Parser)]
+#[command(version, about, long_about = None)]
+struct Cli {
+    /// CSV of QCArchive record ID, value pairs, such as the change in error
+    /// from an old force field to a new one
+    records: PathBuf,
+
+    /// The dataset JSON file the records came from
+    dataset: PathBuf,
+
+    /// The force field to label the dataset with
+    forcefield: String,
+
+    /// Records with values at or below this threshold are improved
+    #[arg(short, long, allow_hyphen_values = true)]
+    #[arg(required_unless_present = "mcs")]
+    improved: Option<f64>,
+
+    /// Records with values at or above this threshold are degraded
+    #[arg(short, long, allow_hyphen_values = true)]
+    #[arg(required_unless_present = "mcs")]
+    degraded: Option<f64>,
+
+    /// The largest radius of the Morgan atom environments
+    #[arg(short, long, default_value_t = 2)]
+    radius: u32,
+
+    /// Skip features found in fewer than this many records
+    #[arg(short, long, default_value_t = 5)]
+    min_count: usize,
+
+    /// The number of example SMILES to report for each feature
+    #[arg(short, long, default_value_t = 3)]
+    examples: usize,
+
+    /// Instead of the enrichment analysis, search for the maximum common
+    /// substructure of the records with this many highest values, and
+    /// count the records containing it among them and among the rest
+    #[arg(long, conflicts_with_all = ["improved", "degraded"])]
+    #[arg(value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    mcs: Option<usize>,
+
+    /// Only consider records whose molecules are assigned this parameter. The
+    /// handler to label them with is chosen by the prefix of the ID
+    #[arg(short, long, requires = "mcs")]
+    param: Option<String>,
+
+    /// Search the fragment of each record within this many bonds of all of the
+    /// chemical environments assigned `param` instead of the whole molecules,
+    /// and count the records whose fragments contain the common substructure
+    #[arg(long, requires = "param")]
+    #[arg(value_parser = clap::value_parser!(u32).range(1..))]
+    neighborhood: Option<u32>,
+
+    /// The fraction of the searched records that must contain the common
+    /// substructure, greater than 0 and at most 1
+    #[arg(long, default_value_t = 1.0, requires = "mcs")]
+    #[arg(value_parser = fraction)]
+    threshold: f64,
+
+    /// Stop the search after this many seconds
+    #[arg(long, default_value_t = 60, requires = "mcs")]
+    timeout: u32,
 }
 
 #[allow(unused)]
@@ -21,56 +96,239 @@ struct MRecord {
     value: f64,
     smiles: String,
     mol: ROMol,
-    /// associated parameter IDs
-    pids: Vec<String>,
+    /// chemical environments and their assigned parameter IDs
+    labels: HashMap<Vec<usize>, String>,
+}
+
+/// Parse a fraction in (0, 1]
+fn fraction(s: &str) -> Result<f64, String> {
+    let f: f64 = s.parse().map_err(|e| format!("{e}"))?;
+    if f > 0.0 && f <= 1.0 {
+        Ok(f)
+    } else {
+        Err(format!("{f} is not greater than 0 and at most 1"))
+    }
+}
+
+/// The name of the parameter handler for the parameter ID `pid`, by its prefix
+fn handler(pid: &str) -> Option<&'static str> {
+    match pid.chars().next()? {
+        'b' => Some("Bonds"),
+        'a' => Some("Angles"),
+        't' => Some("ProperTorsions"),
+        'i' => Some("ImproperTorsions"),
+        'n' => Some("vdW"),
+        _ => None,
+    }
 }
 
 fn process_records(
     records: Vec<Record>,
     dataset: HashMap<String, String>,
-    params: ParameterMap,
+    params: MultiParameterMap,
 ) -> Vec<MRecord> {
     let map_op = |r: Record| -> MRecord {
         let smiles = dataset.get(&r.id.to_string()).unwrap();
         let mut mol = ROMol::from_smiles(smiles);
         mol.openff_clean();
-        let mut pids: Vec<_> =
-            params.label_molecule(&mol).into_values().collect();
-        pids.sort();
-        pids.dedup();
+        let labels = params.label_molecule(&mol).swap_remove(0);
         MRecord {
             id: r.id,
             value: r.value,
             smiles: smiles.clone(),
             mol,
-            pids,
+            labels,
         }
     };
     records.into_par_iter().map(map_op).collect()
 }
 
+/// The functional groups in [GROUPS] and Morgan atom environments up to the
+/// given `radius` contained in each of `records`
+fn features(records: &[MRecord], radius: u32) -> Vec<HashSet<Feature>> {
+    let groups: Vec<_> = GROUPS
+        .iter()
+        .map(|(name, smarts)| (*name, ROMol::from_smarts(smarts)))
+        .collect();
+    records
+        .par_iter()
+        .map(|r| {
+            let mut ret: HashSet<_> = groups
+                .iter()
+                .filter(|(_, pattern)| {
+                    !find_smarts_matches_mol(&r.mol, pattern).is_empty()
+                })
+                .map(|(name, _)| Feature::Group(name))
+                .collect();
+            ret.extend(
+                fingerprint::morgan_environments(&r.mol, radius)
+                    .into_iter()
+                    .map(Feature::Morgan),
+            );
+            ret
+        })
+        .collect()
+}
+
+/// Search for the maximum common substructure of the `n` worst of `res` and
+/// report how many of the bad and good records contain it
+fn mcs_main(args: &Cli, res: &[MRecord], n: usize) {
+    let pool: Vec<&MRecord> = res
+        .iter()
+        .filter(|r| {
+            args.param
+                .as_ref()
+                .is_none_or(|p| r.labels.values().any(|v| v == p))
+        })
+        .collect();
+    if pool.is_empty() {
+        die!("no records to search");
+    }
+    let values: Vec<_> = pool.iter().map(|r| r.value).collect();
+    let (bad, good) = mcs::split_worst(&values, n);
+    for &i in &bad {
+        let r = pool[i];
+        eprintln!("{} {:.4} {}", r.id, r.value, r.smiles);
+    }
+
+    // with --neighborhood, search and count the fragment around the
+    // environments assigned the parameter in each record instead of the whole
+    // molecule. One fragment per record keeps the threshold a fraction of
+    // records
+    let fragments: Vec<ROMol> = match (args.neighborhood, &args.param) {
+        (Some(radius), Some(param)) => pool
+            .iter()
+            .map(|r| {
+                let atoms: Vec<usize> = r
+                    .labels
+                    .iter()
+                    .filter(|(_, pid)| *pid == param)
+                    .flat_map(|(env, _)| env.iter().copied())
+                    .collect();
+                mcs::neighborhood(&r.mol, &atoms, radius)
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+    let targets: Vec<&ROMol> = if args.neighborhood.is_some() {
+        fragments.iter().collect()
+    } else {
+        pool.iter().map(|r| &r.mol).collect()
+    };
+    let mols: Vec<&ROMol> = bad.iter().map(|&i| targets[i]).collect();
+    let Some(smarts) = mcs::mcs(&mols, args.threshold, args.timeout) else {
+        die!("no common substructure found");
+    };
+
+    let pattern = ROMol::from_smarts(&smarts);
+    let contains = |idx: &[usize]| {
+        idx.par_iter()
+            .filter(|&&i| {
+                !find_smarts_matches_mol(targets[i], &pattern).is_empty()
+            })
+            .count()
+    };
+    let hits = contains(&bad);
+    if (hits as f64) < args.threshold * bad.len() as f64 {
+        warn!(
+            "the common substructure matches only {hits} of the {} searched \
+             records, below the threshold of {}",
+            bad.len(),
+            args.threshold
+        );
+    }
+    println!("mcs {smarts}");
+    println!("bad {hits}/{}", bad.len());
+    println!("good {}/{}", contains(&good), good.len());
+}
+
 fn main() {
-    let args: Vec<_> = std::env::args().collect();
-    if args.len() < 4 {
-        die!("Usage: ffblame <data.csv> <dataset.json> <forcefield.offxml>");
+    env_logger::init();
+
+    let args = Cli::parse();
+    let records = load_csv(&args.records).unwrap_or_else(|e| {
+        die!("failed to load {:?} with {}", args.records, e)
+    });
+    // NaN values would sort past every threshold and end the sets early
+    let total = records.len();
+    let records: Vec<Record> = records
+        .into_iter()
+        .filter(|r| r.value.is_finite())
+        .collect();
+    if records.len() < total {
+        warn!(
+            "skipping {} records with non-finite values",
+            total - records.len()
+        );
+    }
+    let dataset = load_dataset(&args.dataset).unwrap_or_else(|e| {
+        die!("failed to load {:?} with {}", args.dataset, e)
+    });
+    let forcefield = ForceField::load(&args.forcefield).unwrap_or_else(|e| {
+        die!("failed to load {} with {}", args.forcefield, e)
+    });
+    // only the parameter searched for in mcs mode uses the labels
+    let h = match &args.param {
+        Some(p) => {
+            handler(p).unwrap_or_else(|| die!("unrecognized parameter ID {p}"))
+        }
+        None => "ProperTorsions",
+    };
+    let ph = forcefield
+        .get_parameter_handler(h)
+        .unwrap_or_else(|| die!("{} has no {h} handler", args.forcefield));
+    let params = MultiParameterMap::new(
+        KeyOrder::for_handler(h),
+        [ph.parameters().into_iter().map(|p| (p.id(), p.smirks()))],
+    );
+
+    let res = process_records(records, dataset, params);
+    if let Some(n) = args.mcs {
+        mcs_main(&args, &res, n);
+        return;
+    }
+
+    let (Some(improved), Some(degraded)) = (args.improved, args.degraded)
+    else {
+        unreachable!("clap requires both thresholds outside of mcs mode");
+    };
+    if improved >= degraded {
+        die!("the improved threshold must be below the degraded threshold");
+    }
+    let labeled: Vec<_> = res
+        .iter()
+        .map(|r| r.smiles.as_str())
+        .zip(features(&res, args.radius))
+        .collect();
+
+    // the indices of the records in each set, most extreme first, so that
+    // the example SMILES come from the biggest changes
+    let mut order: Vec<usize> = (0..res.len()).collect();
+    order.sort_by(|&a, &b| res[a].value.total_cmp(&res[b].value));
+    let improved: Vec<_> = order
+        .iter()
+        .copied()
+        .take_while(|&i| res[i].value <= improved)
+        .collect();
+    let degraded: Vec<_> = order
+        .iter()
+        .rev()
+        .copied()
+        .take_while(|&i| res[i].value >= degraded)
+        .collect();
+    info!(
+        "{} records: {} improved, {} degraded",
+        res.len(),
+        improved.len(),
+        degraded.len()
+    );
+
+    let mut out = std::io::stdout().lock();
+    let write_err = |e| die!("failed to write enrichment with {e}");
+    writeln!(out, "set,feature,count,in_set,odds_ratio,p,p_adj,examples")
+        .unwrap_or_else(write_err);
+    for (name, set) in [("improved", improved), ("degraded", degraded)] {
+        let e = enrichment(&labeled, &set, args.min_count, args.examples);
+        write_enrichment(&mut out, name, &e).unwrap_or_else(write_err);
     }
-    let records = load_csv(&args[1]).unwrap();
-    let dataset = load_dataset(&args[2]).unwrap();
-    let forcefield = ForceField::load(&args[3]).unwrap();
-    let params: ParameterMap = forcefield
-        .get_parameter_handler("ProperTorsions")
-        .unwrap()
-        .into();
-
-    let _res = process_records(records, dataset, params);
-
-    // let mut errors: HashMap<String, Vec<f64>> = HashMap::new();
-    // for (pid, val) in res {
-    //     errors.entry(pid).or_default().push(val);
-    // }
-
-    // println!("param,mean");
-    // for (pid, errs) in errors {
-    //     println!("{pid},{:.8}", mean(&errs));
-    // }
 }
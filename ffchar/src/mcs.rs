@@ -0,0 +1,53 @@
+//! Maximum common substructure search over the worst records
+
+use rdkit_rs::{find_mcs, ROMol};
+
+#[cfg(test)]
+mod tests;
+
+/// Split the indices of `values` into the `n` highest, in decreasing order, and
+/// the rest, in their original order
+pub(crate) fn split_worst(
+    values: &[f64],
+    n: usize,
+) -> (Vec<usize>, Vec<usize>) {
+    let mut order: Vec<usize> = (0..values.len()).collect();
+    order.sort_by(|&a, &b| values[b].total_cmp(&values[a]));
+    let mut rest = order.split_off(n.min(values.len()));
+    rest.sort();
+    (order, rest)
+}
+
+/// The SMARTS pattern of the maximum common substructure found in at least a
+/// `threshold` fraction of `mols` within `timeout` seconds, if any
+pub(crate) fn mcs(
+    mols: &[&ROMol],
+    threshold: f64,
+    timeout: u32,
+) -> Option<String> {
+    let res = find_mcs(mols, threshold, timeout);
+    (res.num_atoms > 0).then_some(res.smarts)
+}
+
+/// The fragment of `mol` within `radius` bonds of any of the atoms in `env`
+pub(crate) fn neighborhood(mol: &ROMol, env: &[usize], radius: u32) -> ROMol {
+    let mut bonds: Vec<usize> = env
+        .iter()
+        .flat_map(|&atom| environment(mol, atom, radius))
+        .collect();
+    bonds.sort();
+    bonds.dedup();
+    mol.path_to_submol(&bonds)
+}
+
+/// The bonds of `mol` within `radius` bonds of `atom`. RDKit returns no bonds
+/// at all when the molecule doesn't extend `radius` bonds from the atom, so
+/// this falls back on the largest radius it does extend to, which then covers
+/// every bond in reach.
+fn environment(mol: &ROMol, atom: usize, radius: u32) -> Vec<usize> {
+    (1..=radius)
+        .rev()
+        .map(|r| mol.find_atom_environment_of_radius_n(r, atom))
+        .find(|bonds| !bonds.is_empty())
+        .unwrap_or_default()
+}
@@ -0,0 +1,43 @@
+use super::*;
+
+use rdkit_rs::find_smarts_matches_mol;
+
+#[test]
+fn test_split_worst() {
+    let values = [0.5, 3.0, -1.0, 2.0, 0.0];
+    assert_eq!(split_worst(&values, 2), (vec![1, 3], vec![0, 2, 4]));
+    assert_eq!(split_worst(&values, 0), (vec![], vec![0, 1, 2, 3, 4]));
+    assert_eq!(split_worst(&values, 10), (vec![1, 3, 0, 4, 2], vec![]));
+}
+
+#[test]
+fn test_mcs() {
+    let mols = [
+        ROMol::from_smiles("CC(=O)NC"),
+        ROMol::from_smiles("CCC(=O)NCC"),
+    ];
+    let refs: Vec<&ROMol> = mols.iter().collect();
+    let smarts = mcs(&refs, 1.0, 10).unwrap();
+    // the common substructure is the whole N-methylacetamide, amide included,
+    // so it matches both molecules but not the matching ester
+    let pattern = ROMol::from_smarts(&smarts);
+    for mol in &mols {
+        assert!(!find_smarts_matches_mol(mol, &pattern).is_empty());
+    }
+    let ester = ROMol::from_smiles("CC(=O)OC");
+    assert!(find_smarts_matches_mol(&ester, &pattern).is_empty());
+}
+
+#[test]
+fn test_neighborhood() {
+    let hexane = ROMol::from_smiles("CCCCCC");
+    let torsion = [0, 1, 2, 3];
+    // one bond past the torsion reaches atom 4 but not atom 5
+    assert_eq!(neighborhood(&hexane, &torsion, 1).to_smiles(), "CCCCC");
+    assert_eq!(neighborhood(&hexane, &torsion, 2).to_smiles(), "CCCCCC");
+    // a radius past the ends of the molecule still includes every bond
+    let ethane = ROMol::from_smiles("CC");
+    assert_eq!(neighborhood(&ethane, &[0, 1], 3).to_smiles(), "CC");
+    let butane = ROMol::from_smiles("CCCC");
+    assert_eq!(neighborhood(&butane, &torsion, 3).to_smiles(), "CCCC");
+}
@@ -0,0 +1,34 @@
+use super::*;
+
+#[test]
+fn test_fraction() {
+    assert_eq!(fraction("1"), Ok(1.0));
+    assert_eq!(fraction("0.5"), Ok(0.5));
+    assert!(fraction("0").is_err());
+    assert!(fraction("1.5").is_err());
+    assert!(fraction("NaN").is_err());
+    assert!(fraction("half").is_err());
+}
+
+#[test]
+fn test_handler() {
+    assert_eq!(handler("b12"), Some("Bonds"));
+    assert_eq!(handler("a3"), Some("Angles"));
+    assert_eq!(handler("t18a"), Some("ProperTorsions"));
+    assert_eq!(handler("i1"), Some("ImproperTorsions"));
+    assert_eq!(handler("n7"), Some("vdW"));
+    assert_eq!(handler("x1"), None);
+    assert_eq!(handler(""), None);
+}
+
+#[test]
+fn test_cli() {
+    let parse = |args: &[&str]| {
+        let base = ["ffchar", "records.csv", "dataset.json", "ff.offxml"];
+        Cli::try_parse_from(base.iter().chain(args))
+    };
+    assert!(parse(&["--mcs", "5"]).is_ok());
+    assert!(parse(&["--mcs", "0"]).is_err());
+    assert!(parse(&["--mcs", "5", "--threshold", "0"]).is_err());
+    assert!(parse(&["--mcs", "5", "--threshold", "0.8"]).is_ok());
+}
@@ -153,8 +153,7 @@ fn main() {
     if cli.torsions {
         td_main(&cli.dataset, ff);
     } else {
-        let params: ParameterMap =
-            ff.get_parameter_handler("ProperTorsions").unwrap().into();
+        let params = ParameterMap::from_handler(&ff, "ProperTorsions").unwrap();
         let dataset = load_dataset(&cli.dataset).unwrap();
         opt_main(dataset, params);
     }
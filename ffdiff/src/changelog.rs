@@ -0,0 +1,345 @@
+//! Render the structural and numeric differences between two force fields as
+//! a changelog
+
+use std::io::{self, Write};
+
+use clap::ValueEnum;
+
+use crate::offxml::Parameter;
+use crate::structure::Structure;
+use crate::usage::Usage;
+
+#[cfg(test)]
+mod tests;
+
+#[derive(Clone, Copy, ValueEnum)]
+pub(crate) enum Format {
+    Markdown,
+    Html,
+}
+
+/// A piece of the text of an [Entry]
+enum Span {
+    Text(String),
+    Code(String),
+}
+
+/// A single line of the changelog, describing a change to one parameter
+struct Entry<'a> {
+    old: Option<&'a Parameter>,
+    new: Option<&'a Parameter>,
+    spans: Vec<Span>,
+}
+
+/// The changes to the parameters of a single handler, grouped under headings
+struct Section<'a> {
+    handler: &'a str,
+    groups: Vec<(&'static str, Vec<Entry<'a>>)>,
+}
+
+pub(crate) struct Changelog<'a> {
+    sections: Vec<Section<'a>>,
+}
+
+fn code(s: &str) -> Span {
+    Span::Code(s.to_owned())
+}
+
+fn text(s: impl Into<String>) -> Span {
+    Span::Text(s.into())
+}
+
+fn quantity(value: f64, unit: &str) -> String {
+    if unit.is_empty() {
+        format!("{value}")
+    } else {
+        format!("{value} {unit}")
+    }
+}
+
+/// The entries describing the changes in value of the fields of each of the
+/// `matched` pairs of parameters, ignoring changes no larger than `threshold`
+fn value_entries<'a>(
+    matched: &[(&'a Parameter, &'a Parameter)],
+    threshold: f64,
+) -> Vec<Entry<'a>> {
+    let mut ret = Vec::new();
+    for &(old, new) in matched {
+        let mut names: Vec<&str> =
+            old.fields.iter().map(|(n, _)| n.as_str()).collect();
+        for (n, _) in &new.fields {
+            if !names.contains(&n.as_str()) {
+                names.push(n);
+            }
+        }
+        for name in names {
+            let get = |p: &'a Parameter| {
+                p.fields.iter().find(|(n, _)| n == name).map(|(_, q)| q)
+            };
+            let change = match (get(old), get(new)) {
+                (Some(a), Some(b)) if (a.value - b.value).abs() > threshold => {
+                    format!(
+                        ": {} → {}",
+                        quantity(a.value, &a.unit),
+                        quantity(b.value, &b.unit)
+                    )
+                }
+                (Some(a), None) => {
+                    format!(": removed, was {}", quantity(a.value, &a.unit))
+                }
+                (None, Some(b)) => {
+                    format!(": added as {}", quantity(b.value, &b.unit))
+                }
+                _ => continue,
+            };
+            ret.push(Entry {
+                old: Some(old),
+                new: Some(new),
+                spans: vec![code(&new.id), text(" "), code(name), text(change)],
+            });
+        }
+    }
+    ret
+}
+
+/// Whether `child` looks like it was split off from `parent`, because its ID
+/// is the parent's ID followed by letters, like `t17a` from `t17`
+fn is_split(parent: &Parameter, child: &Parameter) -> bool {
+    parent.handler == child.handler
+        && child
+            .id
+            .strip_prefix(parent.id.as_str())
+            .is_some_and(|suffix| {
+                !suffix.is_empty()
+                    && suffix.chars().all(|c| c.is_ascii_alphabetic())
+            })
+}
+
+/// Group the `added` parameters split off from parameters in `old` by their
+/// parent, and return them with the remaining additions
+fn splits<'a>(
+    old: impl IntoIterator<Item = &'a Parameter>,
+    added: &[&'a Parameter],
+) -> (Vec<(&'a Parameter, Vec<&'a Parameter>)>, Vec<&'a Parameter>) {
+    let mut rest = added.to_vec();
+    let mut ret = Vec::new();
+    for parent in old {
+        let (children, others) =
+            rest.into_iter().partition(|c| is_split(parent, c));
+        rest = others;
+        if !children.is_empty() {
+            ret.push((parent, children));
+        }
+    }
+    (ret, rest)
+}
+
+impl<'a> Changelog<'a> {
+    /// Describe the structural changes `s` between two force fields and the
+    /// changes in value larger than `threshold` of the parameters they share.
+    /// Added parameters whose IDs extend the ID of an old parameter, like
+    /// `t17a` and `t17b` from `t17`, are reported as splits of that parameter.
+    pub(crate) fn new(s: &Structure<'a>, threshold: f64) -> Self {
+        let old = s
+            .matched
+            .iter()
+            .map(|&(o, _)| o)
+            .chain(s.removed.iter().copied());
+        let (splits, added) = splits(old, &s.added);
+        let groups: Vec<(&'static str, Vec<Entry>)> = vec![
+            (
+                "Added",
+                added
+                    .iter()
+                    .map(|&p| Entry {
+                        old: None,
+                        new: Some(p),
+                        spans: vec![code(&p.id), text(" "), code(&p.smirks)],
+                    })
+                    .collect(),
+            ),
+            (
+                "Split",
+                splits
+                    .iter()
+                    .map(|(parent, children)| {
+                        let mut spans =
+                            vec![code(&parent.id), text(" split into ")];
+                        for (i, c) in children.iter().enumerate() {
+                            if i > 0 {
+                                spans.push(text("/"));
+                            }
+                            spans.push(code(&c.id));
+                        }
+                        Entry {
+                            old: Some(parent),
+                            new: None,
+                            spans,
+                        }
+                    })
+                    .collect(),
+            ),
+            (
+                "Removed",
+                s.removed
+                    .iter()
+                    .map(|&p| Entry {
+                        old: Some(p),
+                        new: None,
+                        spans: vec![code(&p.id), text(" "), code(&p.smirks)],
+                    })
+                    .collect(),
+            ),
+            (
+                "SMIRKS changed",
+                s.smirks
+                    .iter()
+                    .map(|&(o, n)| Entry {
+                        old: Some(o),
+                        new: Some(n),
+                        spans: vec![
+                            code(&n.id),
+                            text(": "),
+                            code(&o.smirks),
+                            text(" → "),
+                            code(&n.smirks),
+                        ],
+                    })
+                    .collect(),
+            ),
+            (
+                "Renumbered",
+                s.renumbered
+                    .iter()
+                    .map(|&(o, n)| Entry {
+                        old: Some(o),
+                        new: Some(n),
+                        spans: vec![code(&o.id), text(" → "), code(&n.id)],
+                    })
+                    .collect(),
+            ),
+            (
+                "Moved",
+                s.moved
+                    .iter()
+                    .map(|&(p, o, n)| Entry {
+                        old: None,
+                        new: Some(p),
+                        spans: vec![
+                            code(&p.id),
+                            text(format!(" from position {o} to {n}")),
+                        ],
+                    })
+                    .collect(),
+            ),
+            ("Values changed", value_entries(&s.matched, threshold)),
+        ];
+
+        // split each group by handler, keeping the handlers in order of first
+        // appearance
+        let mut sections: Vec<Section> = Vec::new();
+        for (title, entries) in groups {
+            for e in entries {
+                let handler = &e.new.or(e.old).unwrap().handler;
+                let section =
+                    match sections.iter().position(|s| s.handler == handler) {
+                        Some(i) => &mut sections[i],
+                        None => {
+                            sections.push(Section {
+                                handler,
+                                groups: Vec::new(),
+                            });
+                            sections.last_mut().unwrap()
+                        }
+                    };
+                match section.groups.last_mut() {
+                    Some((t, es)) if *t == title => es.push(e),
+                    _ => section.groups.push((title, vec![e])),
+                }
+            }
+        }
+        Self { sections }
+    }
+
+    /// The handlers with at least one change
+    pub(crate) fn handlers(&self) -> impl Iterator<Item = &str> {
+        self.sections.iter().map(|s| s.handler)
+    }
+
+    /// Write the changelog to `w` in `format` under the heading `title`,
+    /// annotating each entry with the number of molecules it affects if
+    /// `usage` is provided
+    pub(crate) fn write(
+        &self,
+        w: &mut impl Write,
+        format: Format,
+        title: &str,
+        usage: Option<&Usage>,
+    ) -> io::Result<()> {
+        let render = |spans: &[Span]| -> String {
+            spans
+                .iter()
+                .map(|s| match (s, format) {
+                    (Span::Text(t), Format::Markdown) => t.clone(),
+                    (Span::Code(c), Format::Markdown) => format!("`{c}`"),
+                    (Span::Text(t), Format::Html) => escape(t),
+                    (Span::Code(c), Format::Html) => {
+                        format!("<code>{}</code>", escape(c))
+                    }
+                })
+                .collect()
+        };
+        let annotate = |e: &Entry| match usage {
+            Some(u) => match u.count(e.old, e.new) {
+                1 => " (1 molecule)".to_owned(),
+                n => format!(" ({n} molecules)"),
+            },
+            None => String::new(),
+        };
+
+        match format {
+            Format::Markdown => {
+                writeln!(w, "# {title}")?;
+                for section in &self.sections {
+                    writeln!(w, "\n## {}", section.handler)?;
+                    for (heading, entries) in &section.groups {
+                        writeln!(w, "\n### {heading}\n")?;
+                        for e in entries {
+                            writeln!(
+                                w,
+                                "- {}{}",
+                                render(&e.spans),
+                                annotate(e)
+                            )?;
+                        }
+                    }
+                }
+            }
+            Format::Html => {
+                writeln!(w, "<h1>{}</h1>", escape(title))?;
+                for section in &self.sections {
+                    writeln!(w, "<h2>{}</h2>", escape(section.handler))?;
+                    for (heading, entries) in &section.groups {
+                        writeln!(w, "<h3>{heading}</h3>\n<ul>")?;
+                        for e in entries {
+                            writeln!(
+                                w,
+                                "<li>{}{}</li>",
+                                render(&e.spans),
+                                annotate(e)
+                            )?;
+                        }
+                        writeln!(w, "</ul>")?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
@@ -0,0 +1,81 @@
+use crate::offxml::param;
+use crate::structure::diff;
+
+use super::*;
+
+#[test]
+fn test_changelog() {
+    let old = vec![
+        param(
+            "Bonds",
+            r#"smirks="[#6:1]-[#6:2]" id="b1" length="1.52 * angstrom" k="500.0 * angstrom**-2""#,
+        ),
+        param(
+            "ProperTorsions",
+            r#"smirks="[*:1]-[#6:2]-[#6:3]-[*:4]" id="t1" periodicity1="3" phase1="0.0 * degree" k1="0.2 * mole**-1 * kilocalorie" idivf1="1.0""#,
+        ),
+        param(
+            "ProperTorsions",
+            r#"smirks="[*:1]-[#7:2]-[#6:3]-[*:4]" id="t2" periodicity1="2" phase1="180.0 * degree" k1="1.2 * mole**-1 * kilocalorie" idivf1="1.0""#,
+        ),
+    ];
+    let new = vec![
+        param(
+            "Bonds",
+            r#"smirks="[#6:1]-[#6:2]" id="b1" length="1.5201 * angstrom" k="500.0 * angstrom**-2""#,
+        ),
+        param(
+            "ProperTorsions",
+            r#"smirks="[*:1]-[#6:2]-[#6:3]-[*:4]" id="t1" periodicity1="3" phase1="0.0 * degree" k1="0.2 * mole**-1 * kilocalorie" idivf1="1.0""#,
+        ),
+        param(
+            "ProperTorsions",
+            r#"smirks="[*:1]-[#7:2]-[#6:3]-[*:4]" id="t2" periodicity1="2" phase1="180.0 * degree" k1="0.8 * mole**-1 * kilocalorie" idivf1="1.0""#,
+        ),
+        param(
+            "ProperTorsions",
+            r#"smirks="[*:1]-[#7:2]-[#6:3]=[*:4]" id="t2a" periodicity1="1" phase1="0.0 * degree" k1="0.5 * mole**-1 * kilocalorie" idivf1="1.0""#,
+        ),
+        param(
+            "ProperTorsions",
+            r#"smirks="[*:1]-[#7:2]-[#6:3]#[*:4]" id="t2b" periodicity1="1" phase1="0.0 * degree" k1="0.5 * mole**-1 * kilocalorie" idivf1="1.0""#,
+        ),
+        param(
+            "ProperTorsions",
+            r#"smirks="[*:1]-[#8:2]-[#6:3]-[*:4]" id="t20" periodicity1="1" phase1="0.0 * degree" k1="0.5 * mole**-1 * kilocalorie" idivf1="1.0""#,
+        ),
+    ];
+    let s = diff(&old, &new);
+    let changelog = Changelog::new(&s, 0.01);
+    assert_eq!(changelog.handlers().collect::<Vec<_>>(), ["ProperTorsions"]);
+
+    let mut md = Vec::new();
+    changelog
+        .write(&mut md, Format::Markdown, "Changes", None)
+        .unwrap();
+    let want = "# Changes
+
+## ProperTorsions
+
+### Added
+
+- `t20` `[*:1]-[#8:2]-[#6:3]-[*:4]`
+
+### Split
+
+- `t2` split into `t2a`/`t2b`
+
+### Values changed
+
+- `t2` `n2.k`: 1.2 kilocalorie*mole**-1 → 0.8 kilocalorie*mole**-1
+";
+    assert_eq!(String::from_utf8(md).unwrap(), want);
+
+    let mut html = Vec::new();
+    Changelog::new(&s, 0.0)
+        .write(&mut html, Format::Html, "Changes", None)
+        .unwrap();
+    let html = String::from_utf8(html).unwrap();
+    assert!(html.contains("<h2>Bonds</h2>"));
+    assert!(html.contains("<li><code>b1</code> <code>length</code>: 1.52"));
+}
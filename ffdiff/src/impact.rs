@@ -0,0 +1,124 @@
+//! Rank parameter changes by how much of a dataset they affect
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use clap::ValueEnum;
+use fftools::Pid;
+
+use crate::matrix::Row;
+use crate::usage::Count;
+
+#[cfg(test)]
+mod tests;
+
+/// Which usage count to weight each change by
+#[derive(Clone, Copy, ValueEnum)]
+pub(crate) enum Weight {
+    /// the number of chemical environments assigned the parameter
+    Envs,
+    /// the number of unique molecules assigned the parameter
+    Molecules,
+    /// the number of records whose molecule is assigned the parameter
+    Records,
+}
+
+/// The practical impact of the change in one field of a parameter from the
+/// baseline to another force field
+pub(crate) struct Impact<'a> {
+    pub(crate) row: &'a Row,
+
+    /// the index of the force field compared to the baseline
+    pub(crate) forcefield: usize,
+
+    /// the absolute change from the baseline value
+    pub(crate) change: f64,
+
+    /// the relative change from the baseline value, missing for a baseline
+    /// value of zero
+    pub(crate) relative: Option<f64>,
+
+    pub(crate) count: Count,
+
+    /// the magnitude of the absolute change times the chosen count
+    pub(crate) impact: f64,
+}
+
+/// Weight the absolute change in each of `rows` from the force field at
+/// `baseline` to each of the others by the usage of the parameter in `counts`,
+/// and return the nonzero impacts in decreasing order. The absolute change is
+/// used so that fields with a baseline value of zero, like a phase going from
+/// 0 to 180 degrees, are still ranked. Fields missing from either force field
+/// or whose units changed have no change and are skipped.
+pub(crate) fn rank<'a>(
+    rows: &'a [Row],
+    baseline: usize,
+    counts: &HashMap<(String, Pid), Count>,
+    weight: Weight,
+) -> Vec<Impact<'a>> {
+    let mut ret = Vec::new();
+    for row in rows {
+        let Some(&count) = counts.get(&(row.handler.clone(), row.id.clone()))
+        else {
+            continue;
+        };
+        let w = match weight {
+            Weight::Envs => count.envs,
+            Weight::Molecules => count.molecules,
+            Weight::Records => count.records,
+        };
+        for i in (0..row.values.len()).filter(|&i| i != baseline) {
+            let (Some(change), relative) = row.change(baseline, i) else {
+                continue;
+            };
+            let impact = change.abs() * w as f64;
+            if impact > 0.0 {
+                ret.push(Impact {
+                    row,
+                    forcefield: i,
+                    change,
+                    relative,
+                    count,
+                    impact,
+                });
+            }
+        }
+    }
+    ret.sort_by(|a, b| b.impact.total_cmp(&a.impact));
+    ret
+}
+
+/// Write the first `top` of `impacts`, or all of them if `top` is `None`, to
+/// `w` as a whitespace-separated table, with the force fields named by `names`
+pub(crate) fn write_impacts(
+    w: &mut impl Write,
+    names: &[String],
+    impacts: &[Impact],
+    top: Option<usize>,
+) -> io::Result<()> {
+    writeln!(
+        w,
+        "rank param forcefield change relative envs molecules records impact"
+    )?;
+    let top = top.unwrap_or(impacts.len());
+    for (i, imp) in impacts.iter().take(top).enumerate() {
+        let relative = match imp.relative {
+            Some(r) => format!("{r:.4}"),
+            None => "NA".to_owned(),
+        };
+        writeln!(
+            w,
+            "{} {}.{} {} {:.6} {relative} {} {} {} {:.4}",
+            i + 1,
+            imp.row.id,
+            imp.row.field,
+            names[imp.forcefield],
+            imp.change,
+            imp.count.envs,
+            imp.count.molecules,
+            imp.count.records,
+            imp.impact,
+        )?;
+    }
+    Ok(())
+}
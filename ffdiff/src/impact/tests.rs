@@ -0,0 +1,89 @@
+use super::*;
+
+fn row(id: &str, values: &[Option<f64>]) -> Row {
+    Row {
+        handler: "ProperTorsions".to_owned(),
+        id: id.to_owned(),
+        field: "n2.k".to_owned(),
+        units: vec![String::new()],
+        values: values.to_vec(),
+    }
+}
+
+#[test]
+fn test_rank() {
+    let rows = [
+        // a large change to a rare parameter
+        row("t1", &[Some(1.0), Some(2.0)]),
+        // a small change to a common one
+        row("t2", &[Some(1.0), Some(1.1)]),
+        row("t3", &[Some(1.0), Some(1.0)]),
+        row("t4", &[None, Some(1.0)]),
+        // unused
+        row("t5", &[Some(1.0), Some(3.0)]),
+    ];
+    let count = |envs, molecules, records| Count {
+        envs,
+        molecules,
+        records,
+    };
+    let counts: HashMap<_, _> = [
+        ("t1", count(3, 2, 2)),
+        ("t2", count(200, 50, 100)),
+        ("t3", count(200, 50, 100)),
+        ("t4", count(1, 1, 1)),
+    ]
+    .into_iter()
+    .map(|(id, c)| (("ProperTorsions".to_owned(), id.to_owned()), c))
+    .collect();
+
+    let ids = |impacts: &[Impact]| -> Vec<String> {
+        impacts.iter().map(|i| i.row.id.clone()).collect()
+    };
+    let got = rank(&rows, 0, &counts, Weight::Records);
+    assert_eq!(ids(&got), ["t2", "t1"]);
+    assert!((got[0].impact - 10.0).abs() < 1e-9);
+    assert_eq!(got[1].count, count(3, 2, 2));
+
+    let got = rank(&rows, 1, &counts, Weight::Molecules);
+    assert_eq!(ids(&got), ["t2", "t1"]);
+    assert!((got[1].change + 1.0).abs() < 1e-12);
+    assert!((got[1].relative.unwrap() + 0.5).abs() < 1e-12);
+
+    let mut out = Vec::new();
+    let names = ["a".to_owned(), "b".to_owned()];
+    write_impacts(&mut out, &names, &got, Some(1)).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert_eq!(out.lines().count(), 2);
+    assert!(out.ends_with("1 t2.n2.k a -0.100000 -0.0909 200 50 100 5.0000\n"));
+}
+
+#[test]
+fn test_zero_baseline() {
+    let mut phase = row("t1", &[Some(0.0), Some(180.0)]);
+    phase.field = "n1.phase".to_owned();
+    let rows = [phase, row("t2", &[Some(1.0), Some(2.0)])];
+    let counts: HashMap<_, _> = ["t1", "t2"]
+        .into_iter()
+        .map(|id| {
+            let key = ("ProperTorsions".to_owned(), id.to_owned());
+            let count = Count {
+                envs: 1000,
+                molecules: 500,
+                records: 800,
+            };
+            (key, count)
+        })
+        .collect();
+    let got = rank(&rows, 0, &counts, Weight::Envs);
+    assert_eq!(got.len(), 2);
+    assert_eq!(got[0].row.id, "t1");
+    assert_eq!((got[0].change, got[0].relative), (180.0, None));
+    assert_eq!(got[0].impact, 180_000.0);
+
+    let mut out = Vec::new();
+    write_impacts(&mut out, &["a".to_owned(), "b".to_owned()], &got, None)
+        .unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("\n1 t1.n1.phase b 180.000000 NA 1000 500 800"));
+}
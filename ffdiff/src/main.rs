@@ -1,45 +1,286 @@
-use openff_toolkit::ForceField;
-
-fn get_params(ff: &str) -> Vec<(String, f64)> {
-    let h = ForceField::load(ff)
-        .unwrap()
-        .get_parameter_handler("ProperTorsions")
-        .unwrap();
-    let mut ret = Vec::new();
-    for p in h.parameters() {
-        for (i, k) in p.k().into_iter().enumerate() {
-            ret.push((p.id() + "." + &(i + 1).to_string(), k));
-        }
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use changelog::{Changelog, Format};
+use clap::Parser;
+use fftools::{die, load_dataset};
+use impact::Weight;
+use offxml::Parameter;
+use usage::Usage;
+
+mod changelog;
+mod impact;
+mod matrix;
+mod offxml;
+mod profile;
+mod structure;
+mod usage;
+
+#[cfg(test)]
+mod tests;
+
+#[derive(Parser)]
+#[command(version, about, long_about = None)]
+struct Cli {
+    /// The force fields to compare, as paths to OFFXML files or names of
+    /// installed force fields like `openff-2.1.0.offxml`. The others are
+    /// compared against the baseline
+    #[arg(required = true, num_args = 2..)]
+    forcefields: Vec<String>,
+
+    /// The force field to compare the others against, which must be one of
+    /// the force fields given. Defaults to the first
+    #[arg(short, long)]
+    baseline: Option<String>,
+
+    /// Only print the rows of the value table that differ from the baseline
+    #[arg(long)]
+    changed_only: bool,
+
+    /// Report the parameters added, removed, given new SMIRKS, renumbered, or
+    /// moved within their handler instead of comparing values
+    #[arg(short, long)]
+    structure: bool,
+
+    /// Write a changelog in this format from the baseline to each of the
+    /// other force fields, grouped by handler, instead of comparing values
+    #[arg(short, long, value_enum, conflicts_with = "structure")]
+    changelog: Option<Format>,
+
+    /// Only include changes in value larger than this in the changelog
+    #[arg(long, default_value_t = 0.0, requires = "changelog")]
+    threshold: f64,
+
+    /// A dataset of molecules to measure the effect of changes on. With
+    /// --changelog, annotate each entry with the number of molecules assigned
+    /// the parameter by either force field
+    #[arg(short, long)]
+    dataset: Option<PathBuf>,
+
+    /// Rank the changes in value from the baseline by their absolute size
+    /// times the number of uses of the parameter in the dataset, as labeled by
+    /// the baseline, instead of printing the value table
+    #[arg(short, long, value_enum, requires = "dataset")]
+    #[arg(conflicts_with_all = ["structure", "changelog", "profile"])]
+    impact: Option<Weight>,
+
+    /// Only print this many of the highest-impact changes
+    #[arg(long, requires = "impact")]
+    top: Option<usize>,
+
+    /// Compare the energy curves of the proper and improper torsions instead
+    /// of their parameters, reporting the maximum and RMS deviation of each
+    /// curve from the baseline
+    #[arg(short, long, conflicts_with_all = ["structure", "changelog"])]
+    profile: bool,
+
+    /// The number of dihedral angles at which to evaluate each energy curve
+    #[arg(long, default_value_t = 360, requires = "profile")]
+    grid: usize,
+
+    /// Write the energy curves to this CSV file for plotting
+    #[arg(long, requires = "profile")]
+    curves: Option<PathBuf>,
+}
+
+/// Short names for the force fields at `paths` for column headers: their file
+/// names without the extension, unless two of them share a name, in which case
+/// the paths as given
+fn names(paths: &[String]) -> Vec<String> {
+    let stems: Vec<String> = paths
+        .iter()
+        .map(|path| {
+            Path::new(path)
+                .file_stem()
+                .map_or(path.to_owned(), |s| s.to_string_lossy().into_owned())
+        })
+        .collect();
+    let unique = stems.iter().collect::<HashSet<_>>().len() == stems.len();
+    if unique {
+        stems
+    } else {
+        paths.to_vec()
     }
-    ret
 }
 
-fn main() {
-    let args: Vec<_> = std::env::args().collect();
-    if args.len() < 3 {
-        fftools::die!("Usage: ffdiff <ff1.offxml> <ff2.offxml>...");
+/// Print the deviations of the torsion energy curves in each of the
+/// `forcefields` from those in the one at `baseline`, and optionally write the
+/// curves
+fn profile_main(args: &Cli, forcefields: &[Vec<Parameter>], baseline: usize) {
+    if args.grid == 0 {
+        die!("the grid needs at least one point");
     }
-    let p1 = get_params(&args[1]);
-    let mut ps = Vec::new();
-    for arg in &args[2..] {
-        ps.push(get_params(arg));
+    let grid = profile::grid(args.grid);
+    let torsions: Vec<HashMap<&str, (Vec<f64>, &str)>> = forcefields
+        .iter()
+        .map(|ff| {
+            ff.iter()
+                .filter(|p| profile::TORSIONS.contains(&p.handler.as_str()))
+                .map(|p| {
+                    let (terms, unit) =
+                        profile::terms(p).unwrap_or_else(|e| die!("{e}"));
+                    (p.id.as_str(), (profile::curve(&terms, &grid), unit))
+                })
+                .collect()
+        })
+        .collect();
+    // torsion IDs in the order of the first force field they appear in
+    let mut ids: Vec<&str> = Vec::new();
+    for ff in forcefields {
+        for p in ff {
+            if profile::TORSIONS.contains(&p.handler.as_str())
+                && !ids.contains(&p.id.as_str())
+            {
+                ids.push(&p.id);
+            }
+        }
     }
 
-    print!("param");
-    for a in &args[1..] {
-        print!(" {}", a.strip_suffix(".offxml").unwrap());
+    let others: Vec<usize> =
+        (0..forcefields.len()).filter(|&i| i != baseline).collect();
+    let names = names(&args.forcefields);
+    print!("param unit");
+    for &i in &others {
+        let a = &names[i];
+        print!(" {a}.max {a}.rms");
     }
     println!();
-
-    for (k, v) in p1 {
-        print!("{k} {v}");
-        for p2 in &ps {
-            if let Some((_, v2)) = p2.iter().find(|(n, _)| n == &k) {
-                print!(" {v2}");
-            } else {
-                print!(" NA");
+    for id in &ids {
+        let Some((reference, unit)) = torsions[baseline].get(id) else {
+            continue;
+        };
+        print!("{id} {unit}");
+        for t in others.iter().map(|&i| &torsions[i]) {
+            match t.get(id) {
+                Some((_, u)) if u != unit => {
+                    eprintln!("{id}.k has units of both {unit} and {u}");
+                    print!(" NA NA");
+                }
+                Some((c, _)) => {
+                    let (max, rms) = profile::deviation(reference, c);
+                    print!(" {max:.4} {rms:.4}");
+                }
+                None => print!(" NA NA"),
             }
         }
         println!();
     }
+
+    if let Some(path) = &args.curves {
+        let curves: Vec<_> = ids
+            .iter()
+            .map(|&id| {
+                let cs = torsions
+                    .iter()
+                    .map(|t| t.get(id).map(|(c, _)| c.clone()))
+                    .collect();
+                (id, cs)
+            })
+            .collect();
+        std::fs::File::create(path)
+            .map(std::io::BufWriter::new)
+            .and_then(|mut w| {
+                profile::write_curves(&mut w, &args.forcefields, &grid, &curves)
+            })
+            .unwrap_or_else(|e| die!("failed to write {:?} with {}", path, e));
+    }
+}
+
+fn main() {
+    let args = Cli::parse();
+
+    let forcefields: Vec<Vec<Parameter>> = args
+        .forcefields
+        .iter()
+        .map(|ff| {
+            offxml::load(ff)
+                .unwrap_or_else(|e| die!("failed to load {ff} with {e}"))
+        })
+        .collect();
+    let baseline = match &args.baseline {
+        Some(b) => args
+            .forcefields
+            .iter()
+            .position(|ff| ff == b)
+            .unwrap_or_else(|| die!("baseline {b} is not one of the inputs")),
+        None => 0,
+    };
+    let base = &forcefields[baseline];
+    let base_name = &args.forcefields[baseline];
+    // the other force fields, paired with their names
+    let others = args
+        .forcefields
+        .iter()
+        .zip(&forcefields)
+        .enumerate()
+        .filter(|(i, _)| *i != baseline)
+        .map(|(_, pair)| pair);
+
+    if args.structure {
+        let mut stdout = std::io::stdout().lock();
+        for (name, ff) in others {
+            let s = structure::diff(base, ff);
+            writeln!(stdout, "# {base_name} -> {name}")
+                .and_then(|_| structure::write_structure(&mut stdout, &s))
+                .unwrap_or_else(|e| die!("failed to write output with {e}"));
+        }
+        return;
+    }
+
+    if args.profile {
+        profile_main(&args, &forcefields, baseline);
+        return;
+    }
+
+    if let Some(format) = args.changelog {
+        let dataset = args.dataset.as_ref().map(|path| {
+            load_dataset(path).unwrap_or_else(|e| {
+                die!("failed to load {:?} with {}", path, e)
+            })
+        });
+        let mut stdout = std::io::stdout().lock();
+        for (name, ff) in others {
+            let s = structure::diff(base, ff);
+            let changelog = Changelog::new(&s, args.threshold);
+            let usage = dataset.as_ref().map(|d| {
+                let handlers: Vec<_> = changelog.handlers().collect();
+                Usage::new(d.values().cloned(), base, ff, &handlers)
+            });
+            let title = format!("Changes from {base_name} to {name}");
+            changelog
+                .write(&mut stdout, format, &title, usage.as_ref())
+                .unwrap_or_else(|e| die!("failed to write output with {e}"));
+        }
+        return;
+    }
+
+    let rows =
+        matrix::rows(&forcefields, baseline).unwrap_or_else(|e| die!("{e}"));
+    let names = names(&args.forcefields);
+
+    if let Some(weight) = args.impact {
+        let path = args.dataset.as_ref().unwrap();
+        let dataset = load_dataset(path)
+            .unwrap_or_else(|e| die!("failed to load {:?} with {}", path, e));
+        let counts = usage::count_usage(&dataset, base);
+        let impacts = impact::rank(&rows, baseline, &counts, weight);
+        impact::write_impacts(
+            &mut std::io::stdout().lock(),
+            &names,
+            &impacts,
+            args.top,
+        )
+        .unwrap_or_else(|e| die!("failed to write output with {e}"));
+        return;
+    }
+
+    matrix::write_matrix(
+        &mut std::io::stdout().lock(),
+        &names,
+        baseline,
+        &rows,
+        args.changed_only,
+    )
+    .unwrap_or_else(|e| die!("failed to write output with {e}"));
 }
@@ -0,0 +1,212 @@
+//! Compare the values of every field of every parameter across any number of
+//! force fields
+
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Write};
+
+use crate::offxml::Parameter;
+use crate::structure;
+
+#[cfg(test)]
+mod tests;
+
+/// The values of a single field of a parameter in each force field
+pub(crate) struct Row {
+    pub(crate) handler: String,
+
+    /// the ID of the parameter in the baseline, or in the first force field
+    /// containing it if the baseline does not
+    pub(crate) id: String,
+    pub(crate) field: String,
+
+    /// the distinct units of the values in force field order. More than one
+    /// means the units changed, and the values can't be compared
+    pub(crate) units: Vec<String>,
+    pub(crate) values: Vec<Option<f64>>,
+}
+
+impl Row {
+    /// Whether any of the values differ from the one at `baseline`, including
+    /// by being missing, or the units changed
+    pub(crate) fn changed(&self, baseline: usize) -> bool {
+        self.mixed_units()
+            || self.values.iter().any(|v| *v != self.values[baseline])
+    }
+
+    /// Whether the values were given in more than one unit
+    pub(crate) fn mixed_units(&self) -> bool {
+        self.units.len() > 1
+    }
+
+    /// The absolute and relative changes from the value at `baseline` to the
+    /// value at `i`, if both are present and in the same unit. The relative
+    /// change is also missing for a baseline value of zero.
+    pub(crate) fn change(
+        &self,
+        baseline: usize,
+        i: usize,
+    ) -> (Option<f64>, Option<f64>) {
+        match (self.values[baseline], self.values[i]) {
+            _ if self.mixed_units() => (None, None),
+            (Some(b), Some(v)) => {
+                (Some(v - b), (b != 0.0).then(|| (v - b) / b.abs()))
+            }
+            _ => (None, None),
+        }
+    }
+}
+
+/// Match the parameters of each of `forcefields` to those of the one at
+/// `baseline` by ID or SMIRKS with [structure::diff], and the parameters
+/// missing from the baseline to each other by ID or, failing that, by SMIRKS,
+/// and return one [Row] for each field of each matched parameter. Returns an
+/// error if any force field has two parameters with the same ID in a handler.
+pub(crate) fn rows(
+    forcefields: &[Vec<Parameter>],
+    baseline: usize,
+) -> Result<Vec<Row>, String> {
+    for ff in forcefields {
+        let mut seen = HashSet::new();
+        for p in ff {
+            if !seen.insert((&p.handler, &p.id)) {
+                return Err(format!("duplicate {} ID {}", p.handler, p.id));
+            }
+        }
+    }
+    let n = forcefields.len();
+    let base = &forcefields[baseline];
+    let index: HashMap<(&str, &str), usize> = base
+        .iter()
+        .enumerate()
+        .map(|(i, p)| ((p.handler.as_str(), p.id.as_str()), i))
+        .collect();
+
+    // the matching parameter in each force field, for each parameter
+    let mut params: Vec<Vec<Option<&Parameter>>> = base
+        .iter()
+        .map(|p| {
+            let mut ps = vec![None; n];
+            ps[baseline] = Some(p);
+            ps
+        })
+        .collect();
+    // rows of the parameters missing from the baseline, by handler and ID and
+    // by handler and SMIRKS
+    let mut extra_ids: HashMap<(&str, &str), usize> = HashMap::new();
+    let mut extra_smirks: HashMap<(&str, &str), usize> = HashMap::new();
+    for (i, ff) in forcefields.iter().enumerate() {
+        if i == baseline {
+            continue;
+        }
+        let s = structure::diff(base, ff);
+        for (old, p) in s.matched {
+            params[index[&(old.handler.as_str(), old.id.as_str())]][i] =
+                Some(p);
+        }
+        for p in s.added {
+            let id = (p.handler.as_str(), p.id.as_str());
+            let smirks = (p.handler.as_str(), p.smirks.as_str());
+            let row = extra_ids
+                .get(&id)
+                .or_else(|| extra_smirks.get(&smirks))
+                .copied()
+                .filter(|&row| params[row][i].is_none())
+                .unwrap_or_else(|| {
+                    params.push(vec![None; n]);
+                    params.len() - 1
+                });
+            params[row][i] = Some(p);
+            extra_ids.entry(id).or_insert(row);
+            extra_smirks.entry(smirks).or_insert(row);
+        }
+    }
+
+    let mut ret = Vec::new();
+    for ps in params {
+        let first = ps[baseline]
+            .or_else(|| ps.iter().flatten().next().copied())
+            .unwrap();
+        let id = &first.id;
+        let mut names: Vec<&str> = Vec::new();
+        for p in ps.iter().flatten() {
+            for (name, _) in &p.fields {
+                if !names.contains(&name.as_str()) {
+                    names.push(name);
+                }
+            }
+        }
+        for name in names {
+            let mut units: Vec<String> = Vec::new();
+            let mut values = Vec::with_capacity(n);
+            for p in &ps {
+                let q = p
+                    .and_then(|p| p.fields.iter().find(|(f, _)| f == name))
+                    .map(|(_, q)| q);
+                if let Some(q) = q {
+                    if !units.contains(&q.unit) {
+                        units.push(q.unit.clone());
+                    }
+                }
+                values.push(q.map(|q| q.value));
+            }
+            ret.push(Row {
+                handler: first.handler.clone(),
+                id: id.clone(),
+                field: name.to_owned(),
+                units,
+                values,
+            });
+        }
+    }
+    Ok(ret)
+}
+
+/// Write `rows` to `w` as a whitespace-separated table with a column of values
+/// for each of the force fields in `names`, followed by the absolute and
+/// relative changes from the one at `baseline` for each of the others. Rows
+/// whose units changed list every unit separated by `|`, with no changes. Rows
+/// without any change are skipped if `changed_only` is true.
+pub(crate) fn write_matrix(
+    w: &mut impl Write,
+    names: &[String],
+    baseline: usize,
+    rows: &[Row],
+    changed_only: bool,
+) -> io::Result<()> {
+    let others: Vec<usize> =
+        (0..names.len()).filter(|&i| i != baseline).collect();
+    write!(w, "param unit {}", names.join(" "))?;
+    for &i in &others {
+        write!(w, " {0}.abs {0}.rel", names[i])?;
+    }
+    writeln!(w)?;
+
+    let na = |v: Option<f64>, prec: usize| match v {
+        Some(v) => format!("{v:.prec$}"),
+        None => "NA".to_owned(),
+    };
+    for row in rows {
+        if changed_only && !row.changed(baseline) {
+            continue;
+        }
+        let unit: Vec<_> = row
+            .units
+            .iter()
+            .map(|u| if u.is_empty() { "1" } else { u })
+            .collect();
+        let unit = unit.join("|");
+        write!(w, "{}.{} {unit}", row.id, row.field)?;
+        for v in &row.values {
+            match v {
+                Some(v) => write!(w, " {v}")?,
+                None => write!(w, " NA")?,
+            }
+        }
+        for &i in &others {
+            let (abs, rel) = row.change(baseline, i);
+            write!(w, " {} {}", na(abs, 6), na(rel, 4))?;
+        }
+        writeln!(w)?;
+    }
+    Ok(())
+}
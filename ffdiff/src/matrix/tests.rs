@@ -0,0 +1,120 @@
+use crate::offxml::param;
+
+use super::*;
+
+#[test]
+fn test_matrix() {
+    let ff = |t2: &str, extra: Option<&str>| {
+        let b1 = r#"smirks="[#6:1]-[#6:2]" id="b1" length="1.5 * angstrom" k="500.0 * angstrom**-2""#;
+        let mut ret = vec![param("Bonds", b1)];
+        ret.extend(extra.map(|b| param("Bonds", b)));
+        ret.push(param("ProperTorsions", t2));
+        ret
+    };
+    let forcefields = [
+        ff(
+            r#"smirks="[*:1]-[#6:2]-[#6:3]-[*:4]" id="t2" periodicity1="2" phase1="180.0 * degree" k1="2.0""#,
+            None,
+        ),
+        // renumbered and changed
+        ff(
+            r#"smirks="[*:1]-[#6:2]-[#6:3]-[*:4]" id="t3" periodicity1="2" phase1="180.0 * degree" k1="1.0""#,
+            Some(r#"smirks="[#6:1]=[#6:2]" id="b2" length="1.3 * angstrom""#),
+        ),
+        ff(
+            r#"smirks="[*:1]-[#6:2]-[#6:3]-[*:4]" id="t2" periodicity1="2" phase1="180.0 * degree" k1="3.0""#,
+            Some(r#"smirks="[#6:1]=[#6:2]" id="b2" length="1.4 * angstrom""#),
+        ),
+    ];
+    let rows = rows(&forcefields, 0).unwrap();
+    let got: Vec<_> = rows
+        .iter()
+        .map(|r| (format!("{}.{}", r.id, r.field), r.values.clone()))
+        .collect();
+    assert_eq!(
+        got,
+        [
+            ("b1.length".to_owned(), vec![Some(1.5); 3]),
+            ("b1.k".to_owned(), vec![Some(500.0); 3]),
+            ("t2.n2.phase".to_owned(), vec![Some(180.0); 3]),
+            ("t2.n2.k".to_owned(), vec![Some(2.0), Some(1.0), Some(3.0)]),
+            ("b2.length".to_owned(), vec![None, Some(1.3), Some(1.4)]),
+        ]
+    );
+    assert!(!rows[0].changed(0));
+    assert!(rows[3].changed(0));
+    assert_eq!(rows[3].change(0, 1), (Some(-1.0), Some(-0.5)));
+    assert_eq!(rows[4].change(0, 1), (None, None));
+
+    let mut out = Vec::new();
+    let names = ["a".to_owned(), "b".to_owned(), "c".to_owned()];
+    write_matrix(&mut out, &names, 2, &rows, true).unwrap();
+    let want = "param unit a b c a.abs a.rel b.abs b.rel
+t2.n2.k 1 2 1 3 -1.000000 -0.3333 -2.000000 -0.6667
+b2.length angstrom NA 1.3 1.4 NA NA -0.100000 -0.0714
+";
+    assert_eq!(String::from_utf8(out).unwrap(), want);
+}
+
+#[test]
+fn test_units() {
+    let ff = |k: &str| {
+        vec![param(
+            "Bonds",
+            &format!(r#"smirks="[#6:1]-[#6:2]" id="b1" k="{k}""#),
+        )]
+    };
+    // the same unit in a different order compares equal
+    let forcefields = [
+        ff("500.0 * mole**-1 * kilocalorie * angstrom**-2"),
+        ff("400.0 * angstrom**-2 * kilocalorie * mole**-1"),
+    ];
+    let got = rows(&forcefields, 0).unwrap();
+    assert_eq!(got[0].units, ["angstrom**-2*kilocalorie*mole**-1"]);
+    assert_eq!(got[0].change(0, 1), (Some(-100.0), Some(-0.2)));
+
+    // different units are reported instead of compared
+    let forcefields = [
+        ff("500.0 * kilocalorie * mole**-1"),
+        ff("2000.0 * kilojoule * mole**-1"),
+    ];
+    let rows = rows(&forcefields, 0).unwrap();
+    assert!(rows[0].mixed_units());
+    assert!(rows[0].changed(0));
+    assert_eq!(rows[0].change(0, 1), (None, None));
+    let mut out = Vec::new();
+    let names = ["a".to_owned(), "b".to_owned()];
+    write_matrix(&mut out, &names, 0, &rows, true).unwrap();
+    let want = "param unit a b b.abs b.rel
+b1.k kilocalorie*mole**-1|kilojoule*mole**-1 500 2000 NA NA
+";
+    assert_eq!(String::from_utf8(out).unwrap(), want);
+}
+
+#[test]
+fn test_extra_renumbered() {
+    let ff = |bonds: &[&str]| -> Vec<Parameter> {
+        bonds.iter().map(|b| param("Bonds", b)).collect()
+    };
+    let b1 = r#"smirks="[#6:1]-[#6:2]" id="b1" length="1.5 * angstrom""#;
+    let forcefields = [
+        ff(&[b1]),
+        ff(&[
+            b1,
+            r#"smirks="[#6:1]=[#6:2]" id="b2" length="1.3 * angstrom""#,
+        ]),
+        // b2 is renumbered to b3 in a parameter missing from the baseline
+        ff(&[
+            b1,
+            r#"smirks="[#6:1]=[#6:2]" id="b3" length="1.4 * angstrom""#,
+        ]),
+    ];
+    let got = rows(&forcefields, 0).unwrap();
+    assert_eq!(got.len(), 2);
+    assert_eq!(got[1].id, "b2");
+    assert_eq!(got[1].values, [None, Some(1.3), Some(1.4)]);
+
+    let dup = [ff(&[b1, b1])];
+    let err = rows(&dup, 0).err().unwrap();
+    assert_eq!(err, "duplicate Bonds ID b1");
+}
@@ -0,0 +1,211 @@
+//! Read every numeric field of every parameter in a force field from its
+//! OFFXML attributes, keeping the units
+
+use openff_toolkit::ForceField;
+
+#[cfg(test)]
+mod tests;
+
+/// Attributes identifying a parameter rather than describing it
+const SKIP: [&str; 4] = ["id", "smirks", "parent_id", "name"];
+
+/// A numeric attribute value and its unit, with whitespace removed so that it
+/// prints as a single token and the `*`-separated factors sorted so that equal
+/// units compare equal regardless of the order they were written in. The unit
+/// is empty for dimensionless values like `idivf`.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Quantity {
+    pub(crate) value: f64,
+    pub(crate) unit: String,
+}
+
+impl Quantity {
+    /// Parse an attribute value like `1.09 * angstrom`, returning `None` if it
+    /// does not start with a number
+    fn parse(s: &str) -> Option<Self> {
+        let (value, unit) = s.split_once('*').unwrap_or((s, ""));
+        Some(Self {
+            value: value.trim().parse().ok()?,
+            unit: normalize_unit(unit),
+        })
+    }
+}
+
+/// Remove the whitespace from the unit expression `unit` and sort its factors,
+/// so `mole**-1 * kilocalorie` becomes `kilocalorie*mole**-1`
+fn normalize_unit(unit: &str) -> String {
+    let unit: String = unit.split_whitespace().collect();
+    // protect the exponents from the split on multiplication
+    let unit = unit.replace("**", "^");
+    let mut factors: Vec<_> =
+        unit.split('*').filter(|f| !f.is_empty()).collect();
+    factors.sort();
+    factors.join("*").replace('^', "**")
+}
+
+#[derive(Debug)]
+pub(crate) struct Parameter {
+    /// the tag of the parameter handler, like `ProperTorsions`
+    pub(crate) handler: String,
+
+    /// the parameter ID, falling back on its name or SMIRKS if it has none
+    pub(crate) id: String,
+
+    pub(crate) smirks: String,
+
+    /// the numeric attributes in OFFXML order. Indexed torsion terms are keyed
+    /// by periodicity instead of position, so `k2` with `periodicity2="3"`
+    /// becomes `n3.k`, and the periodicity itself is omitted.
+    pub(crate) fields: Vec<(String, Quantity)>,
+
+    /// the `default_idivf` of the handler, used for torsion terms without
+    /// their own `idivf`
+    pub(crate) default_idivf: Option<f64>,
+}
+
+/// The value of the handler attribute `default_idivf` of `handler`, if it has
+/// one. `auto` stands for the divisor the toolkit applies: 3 for impropers,
+/// which are applied once for each of their three outer atom orders, and 1
+/// otherwise.
+fn default_idivf(
+    handler: &str,
+    attrs: &[(String, String)],
+) -> Result<Option<f64>, String> {
+    let Some((_, v)) = attrs.iter().find(|(name, _)| name == "default_idivf")
+    else {
+        return Ok(None);
+    };
+    match v.trim() {
+        "auto" if handler == "ImproperTorsions" => Ok(Some(3.0)),
+        "auto" => Ok(Some(1.0)),
+        v => v
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("invalid {handler} default_idivf `{v}`")),
+    }
+}
+
+/// Load the parameters of every handler in the force field `name`, which is
+/// either a path to an OFFXML file or the name of an installed force field
+pub(crate) fn load(name: &str) -> Result<Vec<Parameter>, String> {
+    let ff = ForceField::load(name).map_err(|e| e.to_string())?;
+    let mut ret = Vec::new();
+    for handler in ff.registered_parameter_handlers() {
+        let Some(ph) = ff.get_parameter_handler(&handler) else {
+            continue;
+        };
+        let default_idivf = default_idivf(&handler, &ph.attributes())?;
+        for p in ph.parameters() {
+            let mut p = Parameter::new(&handler, &p.attributes())?;
+            p.default_idivf = default_idivf;
+            ret.push(p);
+        }
+    }
+    Ok(ret)
+}
+
+impl Parameter {
+    /// Build a [Parameter] of `handler` from its attributes as written in
+    /// OFFXML, like `("length", "1.09 * angstrom")`
+    pub(crate) fn new(
+        handler: &str,
+        attrs: &[(String, String)],
+    ) -> Result<Self, String> {
+        let attr = |name| {
+            attrs
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, v)| v.as_str())
+        };
+        let Some(smirks) = attr("smirks") else {
+            return Err(format!("{handler} parameter without SMIRKS"));
+        };
+        let id = attr("id").or(attr("name")).unwrap_or(smirks).to_owned();
+        let fields = fields(attrs).map_err(|e| format!("in {id}: {e}"))?;
+        Ok(Self {
+            handler: handler.to_owned(),
+            id,
+            smirks: smirks.to_owned(),
+            fields,
+            default_idivf: None,
+        })
+    }
+}
+
+/// Split an indexed attribute name like `phase2` into `("phase", 2)`
+fn split_index(name: &str) -> Option<(&str, usize)> {
+    let base = name.trim_end_matches(|c: char| c.is_ascii_digit());
+    if base.is_empty() || base.len() == name.len() {
+        return None;
+    }
+    Some((base, name[base.len()..].parse().ok()?))
+}
+
+fn fields(
+    attrs: &[(String, String)],
+) -> Result<Vec<(String, Quantity)>, String> {
+    // map from term index to periodicity
+    let mut periodicities = Vec::new();
+    for (name, value) in attrs {
+        if let Some(("periodicity", i)) = split_index(name) {
+            let n = Quantity::parse(value)
+                .ok_or(format!("invalid periodicity `{value}`"))?;
+            if periodicities.iter().any(|&(_, m)| m == n.value) {
+                return Err(format!("duplicate periodicity {}", n.value));
+            }
+            periodicities.push((i, n.value));
+        }
+    }
+
+    let mut ret = Vec::new();
+    for (name, value) in attrs {
+        let name = name.as_str();
+        if SKIP.contains(&name) {
+            continue;
+        }
+        let Some(q) = Quantity::parse(value) else {
+            continue;
+        };
+        // interpolated torsions have fields like `k1_bondorder2`, indexed by
+        // term and then bond order
+        let (term, suffix) = name
+            .find("_bondorder")
+            .map_or((name, ""), |i| name.split_at(i));
+        let name = match split_index(term) {
+            Some(("periodicity", _)) => continue,
+            Some((base, i)) if !periodicities.is_empty() => {
+                let Some((_, n)) = periodicities.iter().find(|(j, _)| *j == i)
+                else {
+                    return Err(format!("no periodicity for {name}"));
+                };
+                format!("n{n}.{base}{suffix}")
+            }
+            _ => name.to_owned(),
+        };
+        ret.push((name, q));
+    }
+    Ok(ret)
+}
+
+/// Split attributes written as in OFFXML, like `id="b1" length="1.5 *
+/// angstrom"`, into name, value pairs, for tests
+#[cfg(test)]
+pub(crate) fn attrs(s: &str) -> Vec<(String, String)> {
+    s.split('"')
+        .collect::<Vec<_>>()
+        .chunks_exact(2)
+        .map(|c| {
+            (
+                c[0].trim().trim_end_matches('=').to_owned(),
+                c[1].to_owned(),
+            )
+        })
+        .collect()
+}
+
+/// Build a [Parameter] of `handler` from `attrs` written as in OFFXML, for
+/// tests
+#[cfg(test)]
+pub(crate) fn param(handler: &str, attrs: &str) -> Parameter {
+    Parameter::new(handler, &self::attrs(attrs)).unwrap()
+}
@@ -0,0 +1,71 @@
+use super::*;
+
+#[test]
+fn test_new() {
+    let t2 = r#"smirks="[*:1]~[#6:2]=[#6:3]~[*:4]" periodicity1="2" periodicity2="1" phase1="180.0 * degree" phase2="0.0 * degree" id="t2" k1="1.0 * mole**-1 * kilocalorie" k2="0.5 * mole**-1 * kilocalorie" k1_bondorder1="0.1 * mole**-1 * kilocalorie""#;
+    let got = [
+        param(
+            "Bonds",
+            r#"smirks="[#6:1]-[#6:2]" id="b1" length="1.52 * angstrom" k="500.0 * angstrom**-2 * mole**-1 * kilocalorie""#,
+        ),
+        param(
+            "ProperTorsions",
+            r#"smirks="[*:1]-[#6X4:2]-[#6X4:3]-[*:4]" periodicity1="3" phase1="0.0 * degree" id="t1" k1="0.2 * mole**-1 * kilocalorie" idivf1="1.0""#,
+        ),
+        param("ProperTorsions", t2),
+        param(
+            "vdW",
+            r#"smirks="[#1:1]" epsilon="0.0157 * mole**-1 * kilocalorie" rmin_half="0.6 * angstrom""#,
+        ),
+    ];
+    let ids: Vec<_> = got.iter().map(|p| p.id.as_str()).collect();
+    // the SMIRKS stands in for a missing ID
+    assert_eq!(ids, ["b1", "t1", "t2", "[#1:1]"]);
+    assert_eq!(got[1].handler, "ProperTorsions");
+    assert_eq!(got[3].smirks, "[#1:1]");
+
+    let names = |p: &Parameter| -> Vec<String> {
+        p.fields.iter().map(|(n, _)| n.clone()).collect()
+    };
+    assert_eq!(names(&got[0]), ["length", "k"]);
+    assert_eq!(names(&got[1]), ["n3.phase", "n3.k", "n3.idivf"]);
+    assert_eq!(
+        names(&got[2]),
+        ["n2.phase", "n1.phase", "n2.k", "n1.k", "n2.k_bondorder1"]
+    );
+    assert_eq!(
+        got[0].fields[1].1,
+        Quantity {
+            value: 500.0,
+            unit: "angstrom**-2*kilocalorie*mole**-1".to_owned(),
+        }
+    );
+    assert_eq!(got[1].fields[2].1.unit, "");
+
+    let bad = t2.replace(r#"periodicity2="1""#, r#"periodicity2="2""#);
+    assert!(Parameter::new("ProperTorsions", &attrs(&bad)).is_err());
+    assert!(Parameter::new("Bonds", &attrs(r#"id="b1""#)).is_err());
+}
+
+#[test]
+fn test_default_idivf() {
+    let auto = attrs(r#"version="0.4" default_idivf="auto""#);
+    assert_eq!(default_idivf("ProperTorsions", &auto), Ok(Some(1.0)));
+    assert_eq!(default_idivf("ImproperTorsions", &auto), Ok(Some(3.0)));
+    let two = attrs(r#"default_idivf="2.0""#);
+    assert_eq!(default_idivf("ProperTorsions", &two), Ok(Some(2.0)));
+    assert_eq!(default_idivf("Bonds", &attrs(r#"version="0.4""#)), Ok(None));
+    let bad = attrs(r#"default_idivf="half""#);
+    assert!(default_idivf("ProperTorsions", &bad).is_err());
+}
+
+#[test]
+fn test_load_by_name() {
+    let got = load("openff-2.1.0.offxml").unwrap();
+    let t1 = got.iter().find(|p| p.id == "t1").unwrap();
+    assert_eq!(t1.handler, "ProperTorsions");
+    assert!(t1.fields.iter().any(|(name, _)| name == "n3.k"));
+    let i1 = got.iter().find(|p| p.id == "i1").unwrap();
+    assert_eq!(i1.default_idivf, Some(3.0));
+    assert!(load("openff-0.0.0.offxml").is_err());
+}
@@ -0,0 +1,129 @@
+//! Compare torsion parameters by their energy curves instead of their Fourier
+//! coefficients
+
+use std::f64::consts::PI;
+use std::io::{self, Write};
+
+use fftools::stats::{mean, rms};
+
+use crate::offxml::Parameter;
+
+#[cfg(test)]
+mod tests;
+
+/// The handlers whose parameters are Fourier series in a dihedral angle
+pub(crate) const TORSIONS: [&str; 2] = ["ProperTorsions", "ImproperTorsions"];
+
+/// A single cosine term of a torsion, with `phase` in radians
+#[derive(Debug, PartialEq)]
+pub(crate) struct Term {
+    periodicity: f64,
+    k: f64,
+    phase: f64,
+    idivf: f64,
+}
+
+/// Collect the cosine terms of the torsion parameter `p` from its `n*.k`,
+/// `n*.phase`, and `n*.idivf` fields, along with the unit of the force
+/// constants. A missing `idivf` is taken from the handler's `default_idivf`, or
+/// as 1 without one.
+pub(crate) fn terms(p: &Parameter) -> Result<(Vec<Term>, &str), String> {
+    let mut ret: Vec<Term> = Vec::new();
+    let mut unit = "";
+    let idivf = p.default_idivf.unwrap_or(1.0);
+    for (name, q) in &p.fields {
+        let Some((n, field)) = name
+            .strip_prefix('n')
+            .and_then(|s| s.split_once('.'))
+            .and_then(|(n, f)| Some((n.parse::<f64>().ok()?, f)))
+        else {
+            continue;
+        };
+        let term = match ret.iter_mut().find(|t| t.periodicity == n) {
+            Some(t) => t,
+            None => {
+                ret.push(Term {
+                    periodicity: n,
+                    k: 0.0,
+                    phase: 0.0,
+                    idivf,
+                });
+                ret.last_mut().unwrap()
+            }
+        };
+        match field {
+            "k" => {
+                term.k = q.value;
+                unit = &q.unit;
+            }
+            "phase" => {
+                term.phase = match q.unit.as_str() {
+                    "degree" => q.value.to_radians(),
+                    "radian" => q.value,
+                    u => {
+                        return Err(format!("{}: unknown phase unit {u}", p.id))
+                    }
+                }
+            }
+            "idivf" => term.idivf = q.value,
+            _ => {}
+        }
+    }
+    Ok((ret, unit))
+}
+
+/// The torsion energy of `terms` at `phi` radians
+pub(crate) fn energy(terms: &[Term], phi: f64) -> f64 {
+    terms
+        .iter()
+        .map(|t| t.k / t.idivf * (1.0 + (t.periodicity * phi - t.phase).cos()))
+        .sum()
+}
+
+/// `points` angles evenly spaced over a full turn, in radians
+pub(crate) fn grid(points: usize) -> Vec<f64> {
+    (0..points)
+        .map(|i| -PI + 2.0 * PI * i as f64 / points as f64)
+        .collect()
+}
+
+/// Evaluate `terms` at each angle in `grid`
+pub(crate) fn curve(terms: &[Term], grid: &[f64]) -> Vec<f64> {
+    grid.iter().map(|&phi| energy(terms, phi)).collect()
+}
+
+/// The maximum absolute and RMS deviations between the curves `a` and `b`,
+/// after shifting each to a mean of zero. A constant offset changes neither
+/// forces nor relative energies, so it is not counted as a difference.
+pub(crate) fn deviation(a: &[f64], b: &[f64]) -> (f64, f64) {
+    let (sa, sb) = (mean(a), mean(b));
+    let diffs: Vec<f64> =
+        a.iter().zip(b).map(|(x, y)| (x - sa) - (y - sb)).collect();
+    let max = diffs.iter().fold(0.0_f64, |m, d| m.max(d.abs()));
+    (max, rms(&diffs))
+}
+
+/// Write the `curves` of each torsion to `w` as CSV, with one row per angle
+/// in `grid` and one column per force field in `names`. Missing parameters
+/// are left empty.
+pub(crate) fn write_curves(
+    w: &mut impl Write,
+    names: &[String],
+    grid: &[f64],
+    curves: &[(&str, Vec<Option<Vec<f64>>>)],
+) -> io::Result<()> {
+    writeln!(w, "param,phi,{}", names.join(","))?;
+    for (id, curves) in curves {
+        for (i, phi) in grid.iter().enumerate() {
+            write!(w, "{id},{:.1}", phi.to_degrees())?;
+            for c in curves {
+                match c {
+                    Some(c) => write!(w, ",{:.6}", c[i])?,
+                    None => write!(w, ",")?,
+                }
+            }
+            writeln!(w)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,66 @@
+use crate::offxml::param;
+
+use super::*;
+
+#[test]
+fn test_profile() {
+    let params = [
+        param(
+            "ProperTorsions",
+            r#"smirks="[*:1]-[#6:2]-[#6:3]-[*:4]" id="t1" periodicity1="1" phase1="0.0 * degree" k1="1.0 * mole**-1 * kilocalorie" idivf1="1.0""#,
+        ),
+        param(
+            "ProperTorsions",
+            r#"smirks="[*:1]-[#6:2]-[#6:3]-[*:4]" id="t2" periodicity1="1" phase1="180.0 * degree" k1="-1.0 * mole**-1 * kilocalorie" idivf1="1.0""#,
+        ),
+        param(
+            "ProperTorsions",
+            r#"smirks="[*:1]-[#6:2]-[#6:3]-[*:4]" id="t3" periodicity1="3" phase1="0.0 * degree" k1="0.6 * mole**-1 * kilocalorie" idivf1="2.0" periodicity2="1" phase2="0.0 * degree" k2="1.0 * mole**-1 * kilocalorie""#,
+        ),
+    ];
+    let (t3, unit) = terms(&params[2]).unwrap();
+    assert_eq!(unit, "kilocalorie*mole**-1");
+    assert_eq!(
+        t3,
+        [
+            Term {
+                periodicity: 3.0,
+                k: 0.6,
+                phase: 0.0,
+                idivf: 2.0,
+            },
+            Term {
+                periodicity: 1.0,
+                k: 1.0,
+                phase: 0.0,
+                idivf: 1.0,
+            },
+        ]
+    );
+    assert!((energy(&t3, 0.0) - 2.6).abs() < 1e-12);
+    assert!(energy(&t3, PI).abs() < 1e-12);
+
+    // the same curve up to a constant, from different coefficients
+    let grid = grid(36);
+    let c1 = curve(&terms(&params[0]).unwrap().0, &grid);
+    let c2 = curve(&terms(&params[1]).unwrap().0, &grid);
+    let (max, rms) = deviation(&c1, &c2);
+    assert!(max < 1e-12 && rms < 1e-12);
+
+    let c3 = curve(&t3, &grid);
+    let (max, rms) = deviation(&c1, &c3);
+    assert!((max - 0.3).abs() < 1e-12);
+    assert!((rms - 0.3 / 2.0_f64.sqrt()).abs() < 1e-12);
+
+    // impropers without their own idivf share the energy among the three
+    // orders of their outer atoms
+    let mut i1 = param(
+        "ImproperTorsions",
+        r#"smirks="[*:1]~[#6X3:2](~[*:3])~[*:4]" id="i1" periodicity1="2" phase1="180.0 * degree" k1="5.0 * mole**-1 * kilocalorie""#,
+    );
+    i1.default_idivf = Some(3.0);
+    let (t, _) = terms(&i1).unwrap();
+    assert_eq!(t[0].idivf, 3.0);
+    assert!(energy(&t, 0.0).abs() < 1e-12);
+    assert!((energy(&t, PI / 2.0) - 10.0 / 3.0).abs() < 1e-12);
+}
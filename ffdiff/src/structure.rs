@@ -0,0 +1,200 @@
+//! Compare the parameter hierarchies of two force fields rather than their
+//! values
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use crate::offxml::Parameter;
+
+#[cfg(test)]
+mod tests;
+
+/// The structural differences between an old and a new force field
+#[derive(Default)]
+pub(crate) struct Structure<'a> {
+    /// every pair of old and new parameters matched by ID or SMIRKS, in old
+    /// order
+    pub(crate) matched: Vec<(&'a Parameter, &'a Parameter)>,
+
+    /// parameters only in the new force field
+    pub(crate) added: Vec<&'a Parameter>,
+
+    /// parameters only in the old force field
+    pub(crate) removed: Vec<&'a Parameter>,
+
+    /// pairs of old and new parameters with the same ID but different SMIRKS
+    pub(crate) smirks: Vec<(&'a Parameter, &'a Parameter)>,
+
+    /// pairs of old and new parameters with different IDs but the same handler
+    /// and SMIRKS
+    pub(crate) renumbered: Vec<(&'a Parameter, &'a Parameter)>,
+
+    /// parameters whose position relative to the others in their handler
+    /// changed, which can change which parameter a molecule is assigned, along
+    /// with their old and new 1-based positions in the handler
+    pub(crate) moved: Vec<(&'a Parameter, usize, usize)>,
+}
+
+/// Return the indices into `xs` of one of its longest strictly increasing
+/// subsequences
+fn longest_increasing(xs: &[usize]) -> Vec<usize> {
+    // tails[k] is the index of the smallest last element of an increasing
+    // subsequence of length k + 1
+    let mut tails: Vec<usize> = Vec::new();
+    let mut prev = vec![usize::MAX; xs.len()];
+    for (i, &x) in xs.iter().enumerate() {
+        let k = tails.partition_point(|&t| xs[t] < x);
+        if k > 0 {
+            prev[i] = tails[k - 1];
+        }
+        if k == tails.len() {
+            tails.push(i);
+        } else {
+            tails[k] = i;
+        }
+    }
+    let mut ret = Vec::new();
+    let mut i = tails.last().copied().unwrap_or(usize::MAX);
+    while i != usize::MAX {
+        ret.push(i);
+        i = prev[i];
+    }
+    ret.reverse();
+    ret
+}
+
+/// Match the parameters of `old` and `new` by ID, then match the leftovers by
+/// handler and SMIRKS to detect renumbering, and classify the differences
+pub(crate) fn diff<'a>(
+    old: &'a [Parameter],
+    new: &'a [Parameter],
+) -> Structure<'a> {
+    let mut ret = Structure::default();
+
+    let new_ids: HashMap<(&str, &str), usize> = new
+        .iter()
+        .enumerate()
+        .map(|(i, p)| ((p.handler.as_str(), p.id.as_str()), i))
+        .collect();
+    // index in new for each old parameter
+    let mut matched: Vec<Option<usize>> = old
+        .iter()
+        .map(|p| new_ids.get(&(p.handler.as_str(), p.id.as_str())).copied())
+        .collect();
+    let mut taken = vec![false; new.len()];
+    for &j in matched.iter().flatten() {
+        taken[j] = true;
+    }
+
+    let mut new_smirks: HashMap<(&str, &str), Vec<usize>> = HashMap::new();
+    for (j, p) in new.iter().enumerate().filter(|(j, _)| !taken[*j]) {
+        new_smirks
+            .entry((&p.handler, &p.smirks))
+            .or_default()
+            .push(j);
+    }
+    for (i, p) in old.iter().enumerate() {
+        if matched[i].is_some() {
+            continue;
+        }
+        let key = (p.handler.as_str(), p.smirks.as_str());
+        if let Some(j) = new_smirks
+            .get_mut(&key)
+            .and_then(|js| (!js.is_empty()).then(|| js.remove(0)))
+        {
+            matched[i] = Some(j);
+            taken[j] = true;
+            ret.renumbered.push((p, &new[j]));
+        } else {
+            ret.removed.push(p);
+        }
+    }
+    ret.added = new
+        .iter()
+        .enumerate()
+        .filter(|(j, _)| !taken[*j])
+        .map(|(_, p)| p)
+        .collect();
+
+    for (i, p) in old.iter().enumerate() {
+        if let Some(j) = matched[i] {
+            ret.matched.push((p, &new[j]));
+            if p.id == new[j].id && p.smirks != new[j].smirks {
+                ret.smirks.push((p, &new[j]));
+            }
+        }
+    }
+
+    // 1-based positions within each handler, for reporting
+    let positions = |params: &[Parameter]| {
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        params
+            .iter()
+            .map(|p| {
+                let c = counts.entry(&p.handler).or_default();
+                *c += 1;
+                *c
+            })
+            .collect::<Vec<_>>()
+    };
+    let (old_pos, new_pos) = (positions(old), positions(new));
+    let mut by_handler: HashMap<&str, Vec<(usize, usize)>> = HashMap::new();
+    for (i, j) in matched.iter().enumerate() {
+        if let Some(j) = j {
+            by_handler
+                .entry(old[i].handler.as_str())
+                .or_default()
+                .push((*j, i));
+        }
+    }
+    let mut handlers: Vec<_> = by_handler.into_iter().collect();
+    handlers.sort_by_key(|(_, pairs)| pairs[0].1);
+    for (_, mut pairs) in handlers {
+        // pairs in new order; the parameters in the longest run that is also
+        // in old order stayed put, and the rest moved. only matched parameters
+        // are considered, so additions and removals alone are not moves
+        pairs.sort();
+        let olds: Vec<usize> = pairs.iter().map(|&(_, i)| i).collect();
+        let stayed = longest_increasing(&olds);
+        let mut moved: Vec<_> = (0..pairs.len())
+            .filter(|k| stayed.binary_search(k).is_err())
+            .map(|k| {
+                let (j, i) = pairs[k];
+                (&new[j], old_pos[i], new_pos[j])
+            })
+            .collect();
+        moved.sort_by_key(|&(_, _, n)| n);
+        ret.moved.extend(moved);
+    }
+
+    ret
+}
+
+/// Write `s` to `w` with one section per category, and one parameter per line
+/// within each section
+pub(crate) fn write_structure(
+    w: &mut impl Write,
+    s: &Structure,
+) -> io::Result<()> {
+    writeln!(w, "added {}", s.added.len())?;
+    for p in &s.added {
+        writeln!(w, "    {} {} {}", p.handler, p.id, p.smirks)?;
+    }
+    writeln!(w, "removed {}", s.removed.len())?;
+    for p in &s.removed {
+        writeln!(w, "    {} {} {}", p.handler, p.id, p.smirks)?;
+    }
+    writeln!(w, "smirks {}", s.smirks.len())?;
+    for (o, n) in &s.smirks {
+        writeln!(w, "    {} {} {} -> {}", n.handler, n.id, o.smirks, n.smirks)?;
+    }
+    writeln!(w, "renumbered {}", s.renumbered.len())?;
+    for (o, n) in &s.renumbered {
+        writeln!(w, "    {} {} -> {} {}", n.handler, o.id, n.id, n.smirks)?;
+    }
+    writeln!(w, "moved {}", s.moved.len())?;
+    for (p, o, n) in &s.moved {
+        writeln!(w, "    {} {} {o} -> {n}", p.handler, p.id)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,61 @@
+use super::*;
+
+fn param(handler: &str, id: &str, smirks: &str) -> Parameter {
+    Parameter {
+        handler: handler.to_owned(),
+        id: id.to_owned(),
+        smirks: smirks.to_owned(),
+        fields: Vec::new(),
+        default_idivf: None,
+    }
+}
+
+#[test]
+fn test_longest_increasing() {
+    assert_eq!(longest_increasing(&[]), Vec::<usize>::new());
+    assert_eq!(longest_increasing(&[2, 1, 3, 4]), [1, 2, 3]);
+    assert_eq!(longest_increasing(&[0, 4, 1, 2, 5, 3]), [0, 2, 3, 5]);
+}
+
+#[test]
+fn test_diff() {
+    let t = "ProperTorsions";
+    let old = [
+        param("Bonds", "b1", "[#6:1]-[#6:2]"),
+        param(t, "t1", "s1"),
+        param(t, "t2", "s2"),
+        param(t, "t3", "s3"),
+        param(t, "t4", "s4"),
+        param(t, "t5", "s5"),
+    ];
+    let new = [
+        param("Bonds", "b1", "[#6:1]-[#6:2]"),
+        param(t, "t2", "s2"),
+        param(t, "t1", "s1"),
+        param(t, "t3", "s3x"),
+        param(t, "t6", "s4"),
+        param(t, "t7", "s7"),
+    ];
+    let s = diff(&old, &new);
+    let ids = |ps: &[&Parameter]| -> Vec<String> {
+        ps.iter().map(|p| p.id.clone()).collect()
+    };
+    assert_eq!(s.matched.len(), 5);
+    assert_eq!(ids(&s.added), ["t7"]);
+    assert_eq!(ids(&s.removed), ["t5"]);
+    assert_eq!(s.smirks.len(), 1);
+    assert_eq!(s.smirks[0].1.smirks, "s3x");
+    assert_eq!(s.renumbered.len(), 1);
+    assert_eq!(
+        (&*s.renumbered[0].0.id, &*s.renumbered[0].1.id),
+        ("t4", "t6")
+    );
+    assert_eq!(s.moved.len(), 1);
+    assert_eq!(
+        (&*s.moved[0].0.id, s.moved[0].1, s.moved[0].2),
+        ("t2", 2, 1)
+    );
+
+    let s = diff(&old, &old);
+    assert!(s.added.is_empty() && s.removed.is_empty() && s.moved.is_empty());
+}
@@ -0,0 +1,14 @@
+use super::*;
+
+#[test]
+fn test_names() {
+    let paths = |ps: &[&str]| -> Vec<String> {
+        ps.iter().map(|p| p.to_string()).collect()
+    };
+    assert_eq!(
+        names(&paths(&["a/openff-2.1.0.offxml", "openff-2.2.0.offxml"])),
+        ["openff-2.1.0", "openff-2.2.0"]
+    );
+    let same = paths(&["a/openff.offxml", "b/openff.offxml"]);
+    assert_eq!(names(&same), same);
+}
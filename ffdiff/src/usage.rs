@@ -0,0 +1,155 @@
+//! Count the molecules in a dataset affected by a change to a parameter
+
+use std::collections::{HashMap, HashSet};
+
+use fftools::parameter_map::{KeyOrder, MultiParameterMap};
+use fftools::{Pid, Smiles};
+use rayon::prelude::*;
+use rdkit_rs::ROMol;
+
+use crate::offxml::Parameter;
+
+#[cfg(test)]
+mod tests;
+
+/// The parameters assigned to each molecule in a dataset by an old and a new
+/// force field, keyed by handler and parameter ID
+pub(crate) struct Usage {
+    molecules: Vec<[HashSet<(String, Pid)>; 2]>,
+}
+
+/// The IDs and SMIRKS patterns of the parameters of `handler` in `params`
+fn handler_params<'a>(
+    params: &'a [Parameter],
+    handler: &'a str,
+) -> impl Iterator<Item = (Pid, String)> + 'a {
+    params
+        .iter()
+        .filter(move |p| p.handler == handler)
+        .map(|p| (p.id.clone(), p.smirks.clone()))
+}
+
+/// One map per handler in `handlers` labeling molecules with the parameters in
+/// each of `forcefields`
+fn parameter_maps<'a>(
+    forcefields: &[&[Parameter]],
+    handlers: &[&'a str],
+) -> Vec<(&'a str, MultiParameterMap)> {
+    handlers
+        .iter()
+        .map(|&h| {
+            let ffs = forcefields.iter().map(|ff| handler_params(ff, h));
+            (h, MultiParameterMap::new(KeyOrder::for_handler(h), ffs))
+        })
+        .collect()
+}
+
+impl Usage {
+    /// Label each of the unique `smiles` with the parameters of `handlers` in
+    /// both the `old` and `new` force fields
+    pub(crate) fn new(
+        smiles: impl IntoIterator<Item = Smiles>,
+        old: &[Parameter],
+        new: &[Parameter],
+        handlers: &[&str],
+    ) -> Self {
+        let smiles: HashSet<Smiles> = smiles.into_iter().collect();
+        // most patterns are shared by the two force fields, so match them
+        // once for both
+        let maps = parameter_maps(&[old, new], handlers);
+        let molecules = smiles
+            .into_par_iter()
+            .map(|smiles| {
+                let mut mol = ROMol::from_smiles(&smiles);
+                mol.openff_clean();
+                let mut ret: [HashSet<(String, Pid)>; 2] = Default::default();
+                for (handler, map) in &maps {
+                    let labels = map.label_molecule(&mol);
+                    for (set, labels) in ret.iter_mut().zip(labels) {
+                        set.extend(
+                            labels
+                                .into_values()
+                                .map(|pid| (handler.to_string(), pid)),
+                        );
+                    }
+                }
+                ret
+            })
+            .collect();
+        Self { molecules }
+    }
+
+    /// The number of molecules assigned `old` by the old force field or `new`
+    /// by the new one
+    pub(crate) fn count(
+        &self,
+        old: Option<&Parameter>,
+        new: Option<&Parameter>,
+    ) -> usize {
+        let key = |p: &Parameter| (p.handler.clone(), p.id.clone());
+        let old = old.map(key);
+        let new = new.map(key);
+        self.molecules
+            .iter()
+            .filter(|[o, n]| {
+                old.as_ref().is_some_and(|k| o.contains(k))
+                    || new.as_ref().is_some_and(|k| n.contains(k))
+            })
+            .count()
+    }
+}
+
+/// The number of chemical environments, molecules, and records in a dataset
+/// assigned a parameter
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct Count {
+    pub(crate) envs: usize,
+    pub(crate) molecules: usize,
+    pub(crate) records: usize,
+}
+
+/// Label each molecule in `dataset`, a map of record IDs to SMILES, with every
+/// handler in `params` and count the uses of each parameter, keyed by handler
+/// and parameter ID. Records sharing a SMILES are labeled once.
+pub(crate) fn count_usage(
+    dataset: &HashMap<String, Smiles>,
+    params: &[Parameter],
+) -> HashMap<(String, Pid), Count> {
+    let mut records: HashMap<&Smiles, usize> = HashMap::new();
+    for smiles in dataset.values() {
+        *records.entry(smiles).or_default() += 1;
+    }
+    let mut handlers: Vec<&str> = Vec::new();
+    for p in params {
+        if !handlers.contains(&p.handler.as_str()) {
+            handlers.push(&p.handler);
+        }
+    }
+    let maps = parameter_maps(&[params], &handlers);
+    let labels: Vec<_> = records
+        .into_par_iter()
+        .map(|(smiles, n)| {
+            let mut mol = ROMol::from_smiles(smiles);
+            mol.openff_clean();
+            let mut envs: HashMap<(String, Pid), usize> = HashMap::new();
+            for (handler, map) in &maps {
+                let labels = map.label_molecule(&mol).swap_remove(0);
+                for pid in labels.into_values() {
+                    *envs.entry((handler.to_string(), pid)).or_default() += 1;
+                }
+            }
+            (n, envs)
+        })
+        .collect();
+
+    let mut ret: HashMap<(String, Pid), Count> = HashMap::new();
+    for (n, envs) in labels {
+        for (key, e) in envs {
+            let count = ret.entry(key).or_default();
+            count.envs += e;
+            count.molecules += 1;
+            count.records += n;
+        }
+    }
+    ret
+}
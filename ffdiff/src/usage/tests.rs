@@ -0,0 +1,73 @@
+use crate::offxml::param;
+
+use super::*;
+
+fn keys(pids: &[&str]) -> HashSet<(String, Pid)> {
+    pids.iter()
+        .map(|p| ("Bonds".to_owned(), p.to_string()))
+        .collect()
+}
+
+#[test]
+fn test_count() {
+    let params = [
+        param(
+            "Bonds",
+            r#"smirks="[#6:1]-[#6:2]" id="b1" length="1.5 * angstrom""#,
+        ),
+        param(
+            "Bonds",
+            r#"smirks="[#6:1]-[#8:2]" id="b2" length="1.4 * angstrom""#,
+        ),
+    ];
+    let usage = Usage {
+        molecules: vec![
+            [keys(&["b1"]), keys(&["b1", "b2"])],
+            [keys(&["b1", "b2"]), keys(&["b1"])],
+            [keys(&[]), keys(&["b2"])],
+        ],
+    };
+    let (b1, b2) = (Some(&params[0]), Some(&params[1]));
+    assert_eq!(usage.count(b1, None), 2);
+    assert_eq!(usage.count(None, b2), 2);
+    assert_eq!(usage.count(b2, b2), 3);
+    assert_eq!(usage.count(None, None), 0);
+}
+
+#[test]
+fn test_count_usage() {
+    let params = [
+        param(
+            "Bonds",
+            r#"smirks="[#6:1]-[#6:2]" id="b1" length="1.5 * angstrom""#,
+        ),
+        param(
+            "Bonds",
+            r#"smirks="[#6:1]-[#8:2]" id="b2" length="1.4 * angstrom""#,
+        ),
+    ];
+    // two conformers of ethanol and one of propane
+    let dataset: HashMap<String, Smiles> =
+        [("1", "CCO"), ("2", "CCO"), ("3", "CCC")]
+            .into_iter()
+            .map(|(id, smiles)| (id.to_owned(), smiles.to_owned()))
+            .collect();
+    let got = count_usage(&dataset, &params);
+    let get = |pid: &str| got[&("Bonds".to_owned(), pid.to_owned())];
+    assert_eq!(
+        get("b1"),
+        Count {
+            envs: 3,
+            molecules: 2,
+            records: 3
+        }
+    );
+    assert_eq!(
+        get("b2"),
+        Count {
+            envs: 1,
+            molecules: 1,
+            records: 2
+        }
+    );
+}
@@ -1,25 +1,88 @@
-//! Track changes in parameter assignment between force fields
+//! Track changes in parameter assignment through a chain of force fields
+
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
 
 use clap::Parser;
-use fftools::{die, load_dataset, parameter_map::ParameterMap};
+use fftools::parameter_map::{KeyOrder, MultiParameterMap};
+use fftools::{die, load_csv, load_dataset};
 use openff_toolkit::ForceField;
 use rayon::iter::{
     IntoParallelIterator, IntoParallelRefIterator, ParallelIterator,
 };
 use rdkit_rs::ROMol;
+use summary::Summary;
+
+mod summary;
+
+#[cfg(test)]
+mod tests;
+
+/// The name given to the parameter of a chemical environment not matched by
+/// a force field
+const UNASSIGNED: &str = "unassigned";
+
+/// The handlers whose assignments are tracked
+const HANDLERS: [&str; 4] =
+    ["Bonds", "Angles", "ProperTorsions", "ImproperTorsions"];
+
+/// The parameters assigned to each chemical environment of a molecule by a
+/// single handler
+type Labels = HashMap<Vec<usize>, String>;
+
+/// Line up the parameters assigned to each chemical environment by each of
+/// the force fields in `labels`, sorted by environment. Environments labeled
+/// by only some of the force fields, like those newly covered or uncovered by
+/// a changed SMIRKS pattern, have [UNASSIGNED] in the others.
+fn align(labels: &[Labels]) -> Vec<(&[usize], Vec<&str>)> {
+    let mut envs: Vec<&Vec<usize>> =
+        labels.iter().flat_map(|l| l.keys()).collect();
+    envs.sort();
+    envs.dedup();
+    envs.into_iter()
+        .map(|k| {
+            let path = labels
+                .iter()
+                .map(|l| l.get(k).map_or(UNASSIGNED, String::as_str))
+                .collect();
+            (k.as_slice(), path)
+        })
+        .collect()
+}
 
 #[derive(Parser)]
 struct Cli {
     #[arg(short, long)]
     dataset: String,
+
+    /// The chain of force fields to follow the assignments through, in order.
+    /// Pass at least two
+    #[arg(short, long = "ff", required = true, num_args = 1..)]
+    forcefields: Vec<String>,
+
+    /// Print a transition matrix counting the environments, molecules, and
+    /// records for each path of assigned parameters, including unchanged
+    /// ones, instead of one line per changed environment
     #[arg(short, long)]
-    ff1: String,
-    #[arg(short, long)]
-    ff2: String,
+    summary: bool,
+
+    /// The number of most common transitions to report in the summary
+    #[arg(long, default_value_t = 10, requires = "summary")]
+    top: usize,
+
+    /// Benchmark CSV files for the first and last force fields in the chain.
+    /// The summary then includes the mean change in the benchmark value of the
+    /// records with each transition
+    #[arg(short, long, num_args = 2, value_names = ["OLD", "NEW"])]
+    #[arg(requires = "summary")]
+    benchmarks: Vec<PathBuf>,
 }
 
 fn main() {
     let args = Cli::parse();
+    if args.forcefields.len() < 2 {
+        die!("need at least two force fields to compare");
+    }
 
     // assign parameters for each record for each force field, then see where
     // they went. going to be similar to ffblame I think with a dataset and
@@ -27,58 +90,110 @@ fn main() {
     let dataset = load_dataset(&args.dataset)
         .unwrap_or_else(|e| die!("failed to load {} with {}", args.dataset, e));
 
-    let molecules: Vec<(String, ROMol)> = dataset
+    let molecules: Vec<(String, String, ROMol)> = dataset
         .into_par_iter()
         .map(|(id, smiles)| {
             let mut mol = ROMol::from_smiles(&smiles);
             mol.openff_clean();
-            (id, mol)
+            (id, smiles, mol)
         })
         .collect();
 
-    let tors = "ProperTorsions";
-
-    let p1: ParameterMap = ForceField::load(&args.ff1)
-        .unwrap()
-        .get_parameter_handler(tors)
-        .unwrap()
-        .into();
+    let forcefields: Vec<ForceField> = args
+        .forcefields
+        .iter()
+        .map(|path| {
+            ForceField::load(path)
+                .unwrap_or_else(|e| die!("failed to load {path} with {e}"))
+        })
+        .collect();
 
-    let p2: ParameterMap = ForceField::load(&args.ff2)
-        .unwrap()
-        .get_parameter_handler(tors)
-        .unwrap()
-        .into();
+    // one map per handler shared by all of the force fields, so that patterns
+    // unchanged along the chain are only matched once
+    let maps: Vec<MultiParameterMap> = HANDLERS
+        .iter()
+        .map(|h| {
+            let order = KeyOrder::for_handler(h);
+            MultiParameterMap::new(
+                order,
+                forcefields.iter().map(|ff| {
+                    ff.get_parameter_handler(h)
+                        .map(|ph| ph.parameters())
+                        .unwrap_or_default()
+                        .into_iter()
+                        .map(|p| (p.id(), p.smirks()))
+                }),
+            )
+        })
+        .collect();
 
+    // the labels for each handler from each force field, for each record
     let results: Vec<_> = molecules
         .par_iter()
-        .map(|(id, mol)| {
-            let l1 = p1.label_molecule(mol);
-            let l2 = p2.label_molecule(mol);
-            #[cfg(debug_assertions)]
-            {
-                // check that the two sets of chemical environments are the
-                // same, otherwise the comparison is wrong and/or meaningless
-                let mut k1: Vec<_> = l1.keys().collect();
-                let mut k2: Vec<_> = l2.keys().collect();
-                k1.sort();
-                k2.sort();
-                assert_eq!(k1, k2);
-            }
-            (id, l1, l2)
+        .map(|(id, smiles, mol)| {
+            let labels: Vec<Vec<Labels>> =
+                maps.iter().map(|m| m.label_molecule(mol)).collect();
+            (id, smiles, labels)
         })
         .collect();
 
+    if args.summary {
+        // record ID to benchmark value, for the old and new force fields
+        let benchmarks: Vec<HashMap<String, f64>> = args
+            .benchmarks
+            .iter()
+            .map(|path| {
+                load_csv(path)
+                    .unwrap_or_else(|e| {
+                        die!("failed to load {:?} with {}", path, e)
+                    })
+                    .into_iter()
+                    .map(|r| (r.id.to_string(), r.value))
+                    .collect()
+            })
+            .collect();
+        let mut summary = Summary {
+            benchmarks: !benchmarks.is_empty(),
+            ..Default::default()
+        };
+        let mut seen = HashSet::new();
+        for (id, smiles, labels) in &results {
+            let paths =
+                labels.iter().flat_map(|l| align(l)).map(|(_, path)| path);
+            let benchmark = match benchmarks.as_slice() {
+                [old, new] => old.get(*id).zip(new.get(*id)),
+                _ => None,
+            };
+            summary.add(
+                &mut seen,
+                smiles,
+                paths,
+                benchmark.map(|(&o, &n)| (o, n)),
+            );
+        }
+        let names: Vec<_> =
+            args.forcefields.iter().map(String::as_str).collect();
+        let order: Vec<_> = (0..forcefields.len())
+            .flat_map(|ff| maps.iter().flat_map(move |m| m.keys(ff)))
+            .map(String::as_str)
+            .chain([UNASSIGNED])
+            .collect();
+        summary
+            .write(&mut std::io::stdout().lock(), &names, &order, args.top)
+            .unwrap_or_else(|e| die!("failed to write summary with {e}"));
+        return;
+    }
+
     // for each molecule, we now have their full vectors of chemical
-    // environments and their assigned parameters for both force fields,
-    // so we should iterate through the environments and see which ones
-    // have different assigned parameters and print those
-
-    for (id, l1, l2) in results {
-        for (k, pid1) in &l1 {
-            let pid2 = l2.get(k).expect("unknown chemical environment");
-            if pid1 != pid2 {
-                println!("{id} {k:?} {pid1} => {pid2}");
+    // environments and their assigned parameters for every force field, so we
+    // should iterate through the environments and print the lineage of the
+    // ones whose assigned parameter changes anywhere along the chain.
+    // environments not matched by a force field come out as `unassigned`
+
+    for (id, _smiles, labels) in results {
+        for (k, path) in labels.iter().flat_map(|l| align(l)) {
+            if !summary::unchanged(&path) {
+                println!("{id} {k:?} {}", path.join(" => "));
             }
         }
     }
@@ -0,0 +1,163 @@
+//! Aggregate individual assignment changes into a transition matrix
+
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Write};
+
+use fftools::Pid;
+
+#[cfg(test)]
+mod tests;
+
+/// The number of chemical environments, unique molecules, and records with a
+/// given transition
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct Counts {
+    pub(crate) envs: usize,
+    pub(crate) molecules: usize,
+    pub(crate) records: usize,
+
+    /// the number of records with benchmark values for both the first and
+    /// last force field
+    pub(crate) benchmarked: usize,
+
+    /// the sum of the changes in benchmark value from the first force field to
+    /// the last
+    pub(crate) delta: f64,
+
+    /// the sum of the changes in the magnitude of the benchmark value, which
+    /// is negative when an error shrinks regardless of its sign
+    pub(crate) delta_abs: f64,
+}
+
+impl Counts {
+    /// The mean change in benchmark value and in its magnitude, if any records
+    /// were benchmarked
+    pub(crate) fn mean_delta(&self) -> Option<(f64, f64)> {
+        let n = self.benchmarked as f64;
+        (self.benchmarked > 0).then(|| (self.delta / n, self.delta_abs / n))
+    }
+}
+
+/// Counts for every path of parameters assigned to the same chemical
+/// environment by the chain of force fields, including unchanged paths
+#[derive(Default)]
+pub(crate) struct Summary {
+    pub(crate) transitions: HashMap<Vec<Pid>, Counts>,
+
+    /// whether to report the benchmark columns
+    pub(crate) benchmarks: bool,
+}
+
+/// Whether `path` assigns the same parameter in every force field
+pub(crate) fn unchanged(path: &[impl PartialEq]) -> bool {
+    path.windows(2).all(|w| w[0] == w[1])
+}
+
+impl Summary {
+    /// Add the transitions of a single record, with the `smiles` of its
+    /// molecule, its path through the force fields for each environment, and
+    /// its benchmark values in the first and last force fields, if known.
+    /// Environments are only counted for the first record of each molecule,
+    /// so that multiple conformers do not inflate the counts.
+    pub(crate) fn add<'a>(
+        &mut self,
+        seen: &mut HashSet<String>,
+        smiles: &str,
+        paths: impl IntoIterator<Item = Vec<&'a str>>,
+        benchmark: Option<(f64, f64)>,
+    ) {
+        let first = seen.insert(smiles.to_owned());
+        let mut here: HashMap<Vec<&str>, usize> = HashMap::new();
+        for path in paths {
+            *here.entry(path).or_default() += 1;
+        }
+        for (path, n) in here {
+            let path = path.into_iter().map(str::to_owned).collect();
+            let c = self.transitions.entry(path).or_default();
+            c.records += 1;
+            if let Some((old, new)) = benchmark {
+                c.benchmarked += 1;
+                c.delta += new - old;
+                c.delta_abs += new.abs() - old.abs();
+            }
+            if first {
+                c.envs += n;
+                c.molecules += 1;
+            }
+        }
+    }
+
+    /// Write the full transition matrix to `w` in sparse form, with a column
+    /// for each of the force fields in `names` and the rows sorted by the
+    /// position of the parameters in `order`, followed by the `top` most
+    /// common paths that change parameter
+    pub(crate) fn write(
+        &self,
+        w: &mut impl Write,
+        names: &[&str],
+        order: &[&str],
+        top: usize,
+    ) -> io::Result<()> {
+        let mut pos: HashMap<&str, usize> = HashMap::new();
+        for (i, &p) in order.iter().enumerate() {
+            pos.entry(p).or_insert(i);
+        }
+        let pos = |pid: &str| pos.get(pid).copied().unwrap_or(order.len());
+        let mut all: Vec<_> = self.transitions.iter().collect();
+        all.sort_by_key(|(path, _)| {
+            path.iter().map(|p| (pos(p), p)).collect::<Vec<_>>()
+        });
+
+        let bench = |c: &Counts| match c.mean_delta() {
+            Some((d, a)) => format!(" {} {d:.4} {a:.4}", c.benchmarked),
+            None => format!(" {} NA NA", c.benchmarked),
+        };
+        write!(w, "{} envs molecules records", names.join(" "))?;
+        if self.benchmarks {
+            write!(w, " benchmarked mean_delta mean_abs_delta")?;
+        }
+        writeln!(w)?;
+        for (path, c) in &all {
+            write!(
+                w,
+                "{} {} {} {}",
+                path.join(" "),
+                c.envs,
+                c.molecules,
+                c.records
+            )?;
+            if self.benchmarks {
+                write!(w, "{}", bench(c))?;
+            }
+            writeln!(w)?;
+        }
+
+        let mut changed: Vec<_> = all
+            .into_iter()
+            .filter(|(path, _)| !unchanged(path))
+            .collect();
+        changed.sort_by(|(_, a), (_, b)| {
+            b.envs.cmp(&a.envs).then(b.records.cmp(&a.records))
+        });
+        writeln!(w, "\nmost common transitions:")?;
+        for (path, c) in changed.into_iter().take(top) {
+            write!(
+                w,
+                "{}: {} environments, {} molecules, {} records",
+                path.join(" => "),
+                c.envs,
+                c.molecules,
+                c.records
+            )?;
+            match c.mean_delta() {
+                Some((d, a)) if self.benchmarks => write!(
+                    w,
+                    ", mean change {d:.4}, mean change in magnitude {a:.4}"
+                )?,
+                _ => {}
+            }
+            writeln!(w)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,95 @@
+use super::*;
+
+#[test]
+fn test_summary() {
+    let mut summary = Summary::default();
+    let mut seen = HashSet::new();
+    // two conformers of the same molecule
+    for _ in 0..2 {
+        summary.add(
+            &mut seen,
+            "CC",
+            [vec!["t1", "t1"], vec!["t1", "t2"], vec!["t1", "t2"]],
+            None,
+        );
+    }
+    summary.add(
+        &mut seen,
+        "CCO",
+        [vec!["t1", "t2"], vec!["unassigned", "t3"]],
+        None,
+    );
+
+    let get = |path: &[&str]| {
+        let path: Vec<_> = path.iter().map(|s| s.to_string()).collect();
+        summary.transitions[&path]
+    };
+    let counts = |envs, molecules, records| Counts {
+        envs,
+        molecules,
+        records,
+        ..Default::default()
+    };
+    assert_eq!(get(&["t1", "t2"]), counts(3, 2, 3));
+    assert_eq!(get(&["t1", "t1"]), counts(1, 1, 2));
+    assert_eq!(get(&["unassigned", "t3"]), counts(1, 1, 1));
+    assert_eq!(summary.transitions.len(), 3);
+
+    let mut out = Vec::new();
+    summary
+        .write(&mut out, &["ff1", "ff2"], &["t1", "t2", "t3"], 5)
+        .unwrap();
+    let want = "ff1 ff2 envs molecules records
+t1 t1 1 1 2
+t1 t2 3 2 3
+unassigned t3 1 1 1
+
+most common transitions:
+t1 => t2: 3 environments, 2 molecules, 3 records
+unassigned => t3: 1 environments, 1 molecules, 1 records
+";
+    assert_eq!(String::from_utf8(out).unwrap(), want);
+
+    // a chain of three force fields
+    let mut summary = Summary::default();
+    summary.add(
+        &mut HashSet::new(),
+        "CC",
+        [vec!["t17", "t17a", "t17a"], vec!["t17", "t17", "t17"]],
+        None,
+    );
+    let mut out = Vec::new();
+    summary
+        .write(&mut out, &["a", "b", "c"], &["t17", "t17a"], 5)
+        .unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.starts_with("a b c envs molecules records\nt17 t17 t17 1"));
+    assert!(out.ends_with(
+        "t17 => t17a => t17a: 1 environments, 1 molecules, 1 records\n"
+    ));
+
+    // benchmark values for the first and last force fields
+    let mut summary = Summary {
+        benchmarks: true,
+        ..Default::default()
+    };
+    let mut seen = HashSet::new();
+    summary.add(&mut seen, "CC", [vec!["t17", "t17a"]], Some((2.0, -1.0)));
+    summary.add(&mut seen, "CC", [vec!["t17", "t17a"]], Some((1.0, 0.5)));
+    summary.add(&mut seen, "CCO", [vec!["t17", "t17a"]], None);
+    let c = summary.transitions[&vec!["t17".to_owned(), "t17a".to_owned()]];
+    assert_eq!((c.records, c.benchmarked), (3, 2));
+    assert_eq!(c.mean_delta(), Some((-1.75, -0.75)));
+
+    let mut out = Vec::new();
+    summary.write(&mut out, &["a", "b"], &[], 5).unwrap();
+    let want = "a b envs molecules records benchmarked mean_delta \
+                mean_abs_delta
+t17 t17a 2 2 3 2 -1.7500 -0.7500
+
+most common transitions:
+t17 => t17a: 2 environments, 2 molecules, 3 records, mean change -1.7500, \
+                mean change in magnitude -0.7500
+";
+    assert_eq!(String::from_utf8(out).unwrap(), want);
+}
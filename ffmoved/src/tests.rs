@@ -0,0 +1,27 @@
+use super::*;
+
+#[test]
+fn test_align() {
+    let labels = |pairs: &[(&[usize], &str)]| -> Labels {
+        pairs
+            .iter()
+            .map(|(k, pid)| (k.to_vec(), pid.to_string()))
+            .collect()
+    };
+    let l1 = labels(&[(&[0, 1, 2, 3], "t1"), (&[1, 2, 3, 4], "t5")]);
+    let l2 = labels(&[(&[0, 1, 2, 3], "t2"), (&[2, 3, 4, 5], "t5")]);
+    let two = [l1.clone(), l2.clone()];
+    let got = align(&two);
+    let want: [(&[usize], Vec<&str>); 3] = [
+        (&[0, 1, 2, 3], vec!["t1", "t2"]),
+        (&[1, 2, 3, 4], vec!["t5", UNASSIGNED]),
+        (&[2, 3, 4, 5], vec![UNASSIGNED, "t5"]),
+    ];
+    assert_eq!(got, want);
+
+    let l3 = labels(&[(&[0, 1, 2, 3], "t2a")]);
+    let three = [l1, l2, l3];
+    let got = align(&three);
+    assert_eq!(got[0].1, ["t1", "t2", "t2a"]);
+    assert_eq!(got[2].1, [UNASSIGNED, "t5", UNASSIGNED]);
+}
@@ -0,0 +1,255 @@
+//! split a dataset into training and test sets at the molecule level, with
+//! every parameter represented in both sets in proportion to its usage
+
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+use clap::Parser;
+use fftools::parameter_map::ParameterMap;
+use fftools::{
+    dataset_smiles, die, filter_dataset, load_dataset_json, Pid, Smiles,
+};
+use log::info;
+use openff_toolkit::ForceField;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+use rdkit_rs::ROMol;
+
+#[cfg(test)]
+mod tests;
+
+/// The valence handlers stratified over by default
+const HANDLERS: [&str; 4] =
+    ["Bonds", "Angles", "ProperTorsions", "ImproperTorsions"];
+
+#[derive(Parser)]
+#[command(version, about, long_about = None)]
+struct Cli {
+    #[arg(short, long)]
+    dataset: PathBuf,
+
+    #[arg(short, long)]
+    forcefield: String,
+
+    /// A parameter handler whose parameters should be represented in both
+    /// sets. Can be repeated, and defaults to all of the valence handlers
+    #[arg(long = "handler", default_values = HANDLERS)]
+    handlers: Vec<String>,
+
+    /// The fraction of the records to put in the test set
+    #[arg(long, default_value_t = 0.2)]
+    test_fraction: f64,
+
+    /// The seed for breaking ties between otherwise equivalent assignments
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+
+    /// Write the two sets to `<output_base>.train.json` and
+    /// `<output_base>.test.json`
+    #[arg(short, long)]
+    output_base: PathBuf,
+
+    #[arg(short, long, default_value_t = 0)]
+    threads: usize,
+}
+
+/// All of the records of a single molecule, which have to end up in the same
+/// set
+struct Molecule {
+    records: Vec<String>,
+    pids: HashSet<Pid>,
+}
+
+/// Group the records in `dataset` by SMILES and label each molecule once with
+/// each of `params`
+fn process_molecules(
+    dataset: HashMap<String, Smiles>,
+    params: &[ParameterMap],
+) -> Vec<Molecule> {
+    let mut by_smiles: HashMap<Smiles, Vec<String>> = HashMap::new();
+    for (rec_id, smiles) in dataset {
+        by_smiles.entry(smiles).or_default().push(rec_id);
+    }
+    let mut ret: Vec<_> = by_smiles
+        .into_par_iter()
+        .map(|(smiles, mut records)| {
+            let mut mol = ROMol::from_smiles(&smiles);
+            mol.openff_clean();
+            records.sort();
+            Molecule {
+                records,
+                pids: params
+                    .iter()
+                    .flat_map(|p| p.label_molecule(&mol).into_values())
+                    .collect(),
+            }
+        })
+        .collect();
+    // the HashMap iteration order is random, so sort for reproducible splits
+    ret.sort_by(|a, b| a.records.cmp(&b.records));
+    ret
+}
+
+/// Assign each of the `molecules` to one of the sets whose target fractions of
+/// the records are given by `fractions`, returning the index of the set for
+/// each molecule.
+///
+/// This is the iterative stratification algorithm of Sechidis et al. (2011),
+/// with each molecule weighted by its number of records. The parameter with the
+/// fewest records left to assign is handled first, and each molecule labeled
+/// with it goes to the set furthest below its target for that parameter, then
+/// below its target size, with remaining ties broken by `rng`.
+fn stratify(
+    molecules: &[Molecule],
+    fractions: &[f64],
+    rng: &mut impl Rng,
+) -> Vec<usize> {
+    let total: usize = molecules.iter().map(|m| m.records.len()).sum();
+    let mut usage: HashMap<&Pid, usize> = HashMap::new();
+    for m in molecules {
+        for pid in &m.pids {
+            *usage.entry(pid).or_default() += m.records.len();
+        }
+    }
+
+    let mut desired_size: Vec<f64> =
+        fractions.iter().map(|f| f * total as f64).collect();
+    let mut desired: Vec<HashMap<&Pid, f64>> = fractions
+        .iter()
+        .map(|f| usage.iter().map(|(&p, &n)| (p, f * n as f64)).collect())
+        .collect();
+
+    let mut order: Vec<usize> = (0..molecules.len()).collect();
+    order.shuffle(rng);
+
+    let mut ret = vec![usize::MAX; molecules.len()];
+    let mut remaining = molecules.len();
+    while remaining > 0 {
+        // the parameter with the fewest remaining records
+        let mut left: HashMap<&Pid, usize> = HashMap::new();
+        for &i in order.iter().filter(|&&i| ret[i] == usize::MAX) {
+            for pid in &molecules[i].pids {
+                *left.entry(pid).or_default() += molecules[i].records.len();
+            }
+        }
+        let pid = left
+            .iter()
+            .min_by(|a, b| a.1.cmp(b.1).then(a.0.cmp(b.0)))
+            .map(|(&pid, _)| pid);
+
+        for &i in &order {
+            if ret[i] != usize::MAX {
+                continue;
+            }
+            let mol = &molecules[i];
+            let s = match pid {
+                Some(pid) if mol.pids.contains(pid) => {
+                    let key = |s: usize| (desired[s][pid], desired_size[s]);
+                    pick(fractions.len(), key, rng)
+                }
+                Some(_) => continue,
+                // once only unlabeled molecules are left, just balance the set
+                // sizes
+                None => pick(fractions.len(), |s| (0.0, desired_size[s]), rng),
+            };
+            let n = mol.records.len() as f64;
+            for p in &mol.pids {
+                *desired[s].get_mut(p).unwrap() -= n;
+            }
+            desired_size[s] -= n;
+            ret[i] = s;
+            remaining -= 1;
+        }
+    }
+    ret
+}
+
+/// Return the index in `0..n` with the largest `key`, breaking ties randomly
+fn pick(
+    n: usize,
+    key: impl Fn(usize) -> (f64, f64),
+    rng: &mut impl Rng,
+) -> usize {
+    let keys: Vec<_> = (0..n).map(key).collect();
+    let best = keys
+        .iter()
+        .copied()
+        .max_by(|a, b| a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1)))
+        .unwrap();
+    let ties: Vec<_> = (0..n).filter(|&i| keys[i] == best).collect();
+    *ties.choose(rng).unwrap()
+}
+
+fn main() {
+    env_logger::init();
+
+    let args = Cli::parse();
+
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(args.threads)
+        .build_global()
+        .expect("failed to initialize thread pool");
+
+    if !(0.0..=1.0).contains(&args.test_fraction) {
+        die!("test fraction must be between 0 and 1");
+    }
+
+    let raw = load_dataset_json(&args.dataset).unwrap_or_else(|e| {
+        die!("failed to load {:?} with {}", args.dataset, e)
+    });
+    let dataset = dataset_smiles(&raw).unwrap_or_else(|e| {
+        die!("failed to load {:?} with {}", args.dataset, e)
+    });
+    let forcefield = ForceField::load(&args.forcefield).unwrap_or_else(|e| {
+        die!("failed to load {} with {}", args.forcefield, e)
+    });
+    let params: Vec<ParameterMap> = args
+        .handlers
+        .iter()
+        .map(|h| {
+            ParameterMap::from_handler(&forcefield, h).unwrap_or_else(|| {
+                die!("{} has no {h} handler", args.forcefield)
+            })
+        })
+        .collect();
+
+    let molecules = process_molecules(dataset, &params);
+
+    let mut rng = StdRng::seed_from_u64(args.seed);
+    let fractions = [1.0 - args.test_fraction, args.test_fraction];
+    let assignment = stratify(&molecules, &fractions, &mut rng);
+
+    let names = ["train", "test"];
+    for (s, name) in names.iter().enumerate() {
+        let keep: HashSet<String> = molecules
+            .iter()
+            .zip(&assignment)
+            .filter(|(_, &a)| a == s)
+            .flat_map(|(m, _)| m.records.iter().cloned())
+            .collect();
+        info!("{name}: {} records", keep.len());
+        let path = args.output_base.with_extension(format!("{name}.json"));
+        std::fs::File::create(&path)
+            .and_then(|f| {
+                Ok(serde_json::to_writer(f, &filter_dataset(&raw, &keep))?)
+            })
+            .unwrap_or_else(|e| die!("failed to write {:?} with {}", path, e));
+    }
+
+    // per-parameter record counts in each set
+    let pids: Vec<&Pid> = params.iter().flat_map(|p| p.keys()).collect();
+    let mut counts: HashMap<&Pid, [usize; 2]> =
+        pids.iter().map(|&pid| (pid, [0, 0])).collect();
+    for (m, &s) in molecules.iter().zip(&assignment) {
+        for pid in &m.pids {
+            counts.get_mut(pid).unwrap()[s] += m.records.len();
+        }
+    }
+    println!("param,train,test");
+    for pid in pids {
+        let [train, test] = counts[pid];
+        println!("{pid},{train},{test}");
+    }
+}
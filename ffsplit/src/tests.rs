@@ -0,0 +1,39 @@
+use super::*;
+
+#[test]
+fn test_stratify() {
+    // 10 molecules with one, two, or three conformers each, labeled with a
+    // common parameter and one rarer parameter
+    let molecules: Vec<_> = (0..10)
+        .map(|i| Molecule {
+            records: (0..i % 3 + 1).map(|j| format!("{i}-{j}")).collect(),
+            pids: ["t1".to_owned(), format!("t{}", 2 + i % 2)]
+                .into_iter()
+                .collect(),
+        })
+        .collect();
+    let mut rng = StdRng::seed_from_u64(0);
+    let got = stratify(&molecules, &[0.7, 0.3], &mut rng);
+
+    assert!(got.iter().all(|&s| s < 2));
+    for pid in ["t1", "t2", "t3"] {
+        for s in 0..2 {
+            assert!(
+                molecules
+                    .iter()
+                    .zip(&got)
+                    .any(|(m, &a)| a == s && m.pids.contains(pid)),
+                "{pid} missing from set {s}"
+            );
+        }
+    }
+
+    let test: usize = molecules
+        .iter()
+        .zip(&got)
+        .filter(|(_, &a)| a == 1)
+        .map(|(m, _)| m.records.len())
+        .sum();
+    // 19 records total
+    assert!((4..=8).contains(&test), "{test} test records");
+}
@@ -0,0 +1,64 @@
+//! Summarize the differences between the in-set and out-set
+
+use std::io::{self, Write};
+
+use fftools::stats::{
+    cliffs_delta, cohens_d, ks_2samp, mann_whitney_u, mean, median, rms,
+};
+
+/// Write a report comparing the values in `in_set` and `out_set` to `w`: the
+/// count, mean, median, and RMS of each set, Kolmogorov-Smirnov and
+/// Mann-Whitney U tests of whether they come from the same distribution, and
+/// Cliff's delta and Cohen's d effect sizes of the in-set relative to the
+/// out-set
+pub(crate) fn write_comparison(
+    w: &mut impl Write,
+    in_set: &[f64],
+    out_set: &[f64],
+) -> io::Result<()> {
+    let set_w = 6;
+    let n_w = 8;
+    let v_w = 12;
+    writeln!(
+        w,
+        "{set:<set_w$} {n:>n_w$} {mean:>v_w$} {median:>v_w$} {rms:>v_w$}",
+        set = "set",
+        n = "count",
+        mean = "mean",
+        median = "median",
+        rms = "rms",
+    )?;
+    for (set, vals) in [("in", in_set), ("out", out_set)] {
+        if vals.is_empty() {
+            writeln!(w, "{set:<set_w$} {:>n_w$}", 0)?;
+            continue;
+        }
+        writeln!(
+            w,
+            "{set:<set_w$} {n:>n_w$} {mean:>v_w$.4} {median:>v_w$.4} \
+             {rms:>v_w$.4}",
+            n = vals.len(),
+            mean = mean(vals),
+            median = median(vals),
+            rms = rms(vals),
+        )?;
+    }
+
+    if in_set.len() < 2 || out_set.len() < 2 {
+        writeln!(w, "\nnot enough records in each set for statistical tests")?;
+        return Ok(());
+    }
+
+    let (d, ks_p) = ks_2samp(in_set, out_set);
+    let (u, mw_p) = mann_whitney_u(in_set, out_set);
+    writeln!(w)?;
+    writeln!(w, "Kolmogorov-Smirnov D = {d:.4}, p = {ks_p:.4e}")?;
+    writeln!(w, "Mann-Whitney U = {u:.1}, p = {mw_p:.4e}")?;
+    writeln!(
+        w,
+        "Cliff's delta = {:.4}, Cohen's d = {:.4}",
+        cliffs_delta(in_set, out_set),
+        cohens_d(in_set, out_set),
+    )?;
+    Ok(())
+}
@@ -0,0 +1,85 @@
+//! Report why each record ended up in its set
+
+use std::io::{self, Write};
+
+use fftools::Record;
+use serde_json::{json, Map, Value};
+
+use crate::groups::Group;
+use crate::select::{Explanation, Expr, Labels};
+
+/// The terms of `expr` satisfied by the molecule labeled with `labels`, as a
+/// JSON array of objects with the term, whether it was negated, and the
+/// environments of each matching parameter ID or SMARTS pattern
+fn matched(expr: &Expr, labels: &Labels) -> Value {
+    expr.explain(labels)
+        .into_iter()
+        .map(
+            |Explanation {
+                 term,
+                 negated,
+                 matches,
+             }| {
+                let envs: Map<String, Value> = matches
+                    .into_iter()
+                    .map(|(key, envs)| (key.to_owned(), json!(envs)))
+                    .collect();
+                json!({
+                    "term": term,
+                    "negated": negated,
+                    "environments": envs,
+                })
+            },
+        )
+        .collect()
+}
+
+/// Write one JSON object per line to `w` for each of the records in `sets`,
+/// giving the name of its set and the terms of `subset` it matched
+pub(crate) fn write_explanations<'a>(
+    w: &mut impl Write,
+    subset: &Expr,
+    sets: impl IntoIterator<Item = (&'a str, &'a [(Record, Labels)])>,
+) -> io::Result<()> {
+    for (set, records) in sets {
+        for (rec, labels) in records {
+            let line = json!({
+                "id": rec.id,
+                "value": rec.value,
+                "set": set,
+                "matched": matched(subset, labels),
+            });
+            writeln!(w, "{line}")?;
+        }
+    }
+    Ok(())
+}
+
+/// Write one JSON object per line to `w` for each of the `labeled` records,
+/// giving the names of the groups it belongs to and the terms of each group's
+/// selector that it matched
+pub(crate) fn write_group_explanations(
+    w: &mut impl Write,
+    groups: &[Group],
+    labeled: &[(Record, Labels)],
+) -> io::Result<()> {
+    for (rec, labels) in labeled {
+        let names: Vec<_> = groups
+            .iter()
+            .filter(|g| g.expr.matches(labels))
+            .map(|g| g.name.as_str())
+            .collect();
+        let matches: Map<String, Value> = groups
+            .iter()
+            .map(|g| (g.name.clone(), matched(&g.expr, labels)))
+            .collect();
+        let line = json!({
+            "id": rec.id,
+            "value": rec.value,
+            "groups": names,
+            "matched": matches,
+        });
+        writeln!(w, "{line}")?;
+    }
+    Ok(())
+}
@@ -0,0 +1,86 @@
+//! Split records into any number of named groups at once
+
+use std::{fs::read_to_string, io, path::Path};
+
+use fftools::Record;
+
+use crate::select::{self, Expr, Labels};
+
+/// The name of the group holding records not selected by any other group
+pub(crate) const UNASSIGNED: &str = "unassigned";
+
+/// A named selector loaded by [load_groups]
+pub(crate) struct Group {
+    pub(crate) name: String,
+    pub(crate) expr: Expr,
+}
+
+/// Load a sequence of [Group]s from `path`, one `name: selector` pair per line.
+/// Blank lines and lines starting with `#` are skipped. Names become file
+/// extensions in the output, so they can't contain whitespace, path
+/// separators, or `.`.
+pub(crate) fn load_groups(path: impl AsRef<Path>) -> io::Result<Vec<Group>> {
+    let invalid = |msg| io::Error::new(io::ErrorKind::InvalidData, msg);
+    let mut ret: Vec<Group> = Vec::new();
+    for line in read_to_string(path)?.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((name, expr)) = line.split_once(':') else {
+            return Err(invalid(format!("expected `name: selector`: {line}")));
+        };
+        let name = name.trim();
+        let bad = |c: char| {
+            c.is_whitespace() || c == '.' || std::path::is_separator(c)
+        };
+        if name.is_empty() || name.contains(bad) {
+            return Err(invalid(format!("invalid group name `{name}`")));
+        }
+        if name == UNASSIGNED || ret.iter().any(|g| g.name == name) {
+            return Err(invalid(format!("duplicate group name `{name}`")));
+        }
+        let expr = select::parse(expr)
+            .map_err(|e| invalid(format!("in group {name}: {e}")))?;
+        ret.push(Group {
+            name: name.to_owned(),
+            expr,
+        });
+    }
+    Ok(ret)
+}
+
+/// Assign each of the `labeled` records to every group whose selector it
+/// matches, returning the indices of the records in each group followed by
+/// the indices of the records matching no group. If `exclusive` is true, a
+/// record matching more than one group is an error.
+pub(crate) fn partition(
+    labeled: &[(Record, Labels)],
+    groups: &[Group],
+    exclusive: bool,
+) -> Result<Vec<Vec<usize>>, String> {
+    let mut ret = vec![Vec::new(); groups.len() + 1];
+    for (i, (rec, labels)) in labeled.iter().enumerate() {
+        let matched: Vec<_> = groups
+            .iter()
+            .enumerate()
+            .filter(|(_, g)| g.expr.matches(labels))
+            .map(|(j, _)| j)
+            .collect();
+        if exclusive && matched.len() > 1 {
+            let names: Vec<_> =
+                matched.iter().map(|&j| groups[j].name.as_str()).collect();
+            return Err(format!(
+                "record {} belongs to several groups: {}",
+                rec.id,
+                names.join(", ")
+            ));
+        }
+        if matched.is_empty() {
+            ret[groups.len()].push(i);
+        }
+        for j in matched {
+            ret[j].push(i);
+        }
+    }
+    Ok(ret)
+}
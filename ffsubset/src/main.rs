@@ -11,73 +11,208 @@ use clap::Parser;
 use rayon::prelude::*;
 
 use fftools::parameter_map::ParameterMap;
-use fftools::{die, load_csv, load_dataset, Record};
+use fftools::{
+    die, filter_dataset, load_csv, load_dataset, load_dataset_json, Record,
+};
 use openff_toolkit::ForceField;
-use rdkit_rs::ROMol;
+use rdkit_rs::{find_smarts_matches_mol, ROMol};
 
 use crate::cli::Cli;
+use crate::groups::{load_groups, UNASSIGNED};
+use crate::select::{Expr, Labels};
+
+mod compare;
+mod explain;
+mod groups;
+mod select;
 
 #[cfg(test)]
 mod tests;
 
-/// Load a sequence of whitespace-separated parameter IDs from `path`
-fn load_subset(path: impl AsRef<Path>) -> io::Result<Vec<String>> {
-    Ok(read_to_string(path)?
-        .split_ascii_whitespace()
-        .map(String::from)
-        .collect())
+/// Load a selector from `path`. See [select] for the syntax, but a plain
+/// sequence of whitespace-separated parameter IDs selects records matching any
+/// of them.
+fn load_subset(path: impl AsRef<Path>) -> io::Result<Expr> {
+    select::parse(&read_to_string(path)?)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
 }
 
-/// Convert a sequence of [Record]s into a sequence of [Record], parameter ID
-/// pairs
+/// Convert a sequence of [Record]s into a sequence of [Record], [Labels] pairs.
+/// Molecules are labeled with `params`, if provided, and searched for each of
+/// the SMARTS patterns in `smarts`
 fn process_records(
     records: Vec<Record>,
     dataset: HashMap<String, String>,
-    params: ParameterMap,
-) -> Vec<(Record, HashSet<String>)> {
-    let map_op = |r: Record| -> (Record, HashSet<String>) {
+    params: Option<ParameterMap>,
+    smarts: &[&str],
+) -> Vec<(Record, Labels)> {
+    let smarts: Vec<_> = smarts
+        .iter()
+        .map(|&s| (s.to_owned(), ROMol::from_smarts(s)))
+        .collect();
+    let map_op = |r: Record| -> (Record, Labels) {
         let smiles = dataset.get(&r.id.to_string()).unwrap();
         let mut mol = ROMol::from_smiles(smiles);
         mol.openff_clean();
-        (r, params.label_molecule(&mol).into_values().collect())
+        let mut labels = Labels::default();
+        if let Some(params) = &params {
+            for (env, pid) in params.label_molecule(&mol) {
+                labels.params.entry(pid).or_default().push(env);
+            }
+        }
+        for (s, pattern) in &smarts {
+            let matches = find_smarts_matches_mol(&mol, pattern);
+            labels.smarts.insert(s.clone(), matches);
+        }
+        (r, labels)
     };
     records.into_par_iter().map(map_op).collect()
 }
 
 struct Output {
-    in_set: Vec<(Record, HashSet<String>)>,
-    out_set: Vec<(Record, HashSet<String>)>,
+    in_set: Vec<(Record, Labels)>,
+    out_set: Vec<(Record, Labels)>,
 }
 
-fn inner<P, Q, R>(records: P, dataset: Q, forcefield: &str, subset: R) -> Output
+/// Load `records` and label their molecules from `dataset` with everything
+/// needed to evaluate `selectors`. The `forcefield` is only needed if one of
+/// the `selectors` refers to parameter IDs
+fn label<P, Q>(
+    records: P,
+    dataset: Q,
+    forcefield: Option<&str>,
+    selectors: &[&Expr],
+) -> Vec<(Record, Labels)>
 where
     P: AsRef<Path> + Debug,
     Q: AsRef<Path> + Debug,
-    R: AsRef<Path> + Debug,
 {
     let records = load_csv(&records)
         .unwrap_or_else(|e| die!("failed to load {:?} with {}", records, e));
     let dataset = load_dataset(&dataset)
         .unwrap_or_else(|e| die!("failed to load {:?} with {}", dataset, e));
-    let forcefield = ForceField::load(forcefield)
-        .unwrap_or_else(|e| die!("failed to load {:?} with {}", forcefield, e));
-    let params: ParameterMap = forcefield
-        .get_parameter_handler("ProperTorsions")
-        .unwrap()
-        .into();
-    let subset: HashSet<_> = load_subset(&subset)
-        .unwrap_or_else(|e| die!("failed to load {:?} with {}", subset, e))
-        .into_iter()
-        .collect();
+    let params: Option<ParameterMap> = forcefield.map(|forcefield| {
+        let ff = ForceField::load(forcefield).unwrap_or_else(|e| {
+            die!("failed to load {:?} with {}", forcefield, e)
+        });
+        ParameterMap::from_handler(&ff, "ProperTorsions").unwrap()
+    });
+    if params.is_none() && selectors.iter().any(|s| s.uses_params()) {
+        die!("a force field is required to select records by parameter ID");
+    }
+    let smarts: Vec<_> = selectors.iter().flat_map(|s| s.smarts()).collect();
+    process_records(records, dataset, params, &smarts)
+}
 
-    let processed_records = process_records(records, dataset, params);
+/// Split `records` into the records selected by `subset` and the rest. The
+/// `forcefield` is only needed if `subset` refers to parameter IDs
+fn inner<P, Q>(
+    records: P,
+    dataset: Q,
+    forcefield: Option<&str>,
+    subset: &Expr,
+) -> Output
+where
+    P: AsRef<Path> + Debug,
+    Q: AsRef<Path> + Debug,
+{
+    let processed_records = label(records, dataset, forcefield, &[subset]);
 
     let (in_set, out_set): (Vec<_>, Vec<_>) = processed_records
         .into_iter()
-        .partition(|(_r, pids)| pids.intersection(&subset).count() > 0);
+        .partition(|(_r, labels)| subset.matches(labels));
     Output { in_set, out_set }
 }
 
+/// Write `records` to `w` as `id,value` lines, prefixed by `prefix` and a comma
+/// if provided
+fn write_records<'a>(
+    w: &mut dyn std::io::Write,
+    prefix: Option<&str>,
+    records: impl IntoIterator<Item = &'a Record>,
+) -> std::io::Result<()> {
+    for rec in records {
+        if let Some(prefix) = prefix {
+            write!(w, "{prefix},")?;
+        }
+        writeln!(w, "{},{}", rec.id, rec.value)?;
+    }
+    Ok(())
+}
+
+/// Write the entries of `dataset` belonging to `records` to `path` as a new
+/// dataset JSON file
+fn write_dataset<'a>(
+    path: impl AsRef<Path>,
+    dataset: &serde_json::Value,
+    records: impl IntoIterator<Item = &'a Record>,
+) -> std::io::Result<()> {
+    let keep: HashSet<String> =
+        records.into_iter().map(|r| r.id.to_string()).collect();
+    let f = std::fs::File::create(path)?;
+    serde_json::to_writer(f, &filter_dataset(dataset, &keep))?;
+    Ok(())
+}
+
+/// Load the raw dataset JSON if `args` asks for subsetted datasets to be
+/// written
+fn dataset_json(args: &Cli) -> Option<serde_json::Value> {
+    args.write_datasets.then(|| {
+        load_dataset_json(&args.dataset).unwrap_or_else(|e| {
+            die!("failed to load {:?} with {}", args.dataset, e)
+        })
+    })
+}
+
+/// Split the records into the [groups::Group]s defined in `path`, writing one
+/// file per group named `<output_base>.<group>` if an output base is provided,
+/// or to stdout with each line prefixed by the group name otherwise
+fn groups_main(args: &Cli, path: &Path) {
+    let groups = load_groups(path)
+        .unwrap_or_else(|e| die!("failed to load {:?} with {}", path, e));
+    let selectors: Vec<_> = groups.iter().map(|g| &g.expr).collect();
+    let labeled = label(
+        &args.records,
+        &args.dataset,
+        args.forcefield.as_deref(),
+        &selectors,
+    );
+    let partition = groups::partition(&labeled, &groups, args.exclusive)
+        .unwrap_or_else(|e| die!("{e}"));
+
+    if let Some(path) = &args.explain {
+        std::fs::File::create(path)
+            .map(std::io::BufWriter::new)
+            .and_then(|mut w| {
+                explain::write_group_explanations(&mut w, &groups, &labeled)
+            })
+            .unwrap_or_else(|e| die!("failed to write {:?} with {}", path, e));
+    }
+
+    let names = groups
+        .iter()
+        .map(|g| g.name.as_str())
+        .chain(std::iter::once(UNASSIGNED));
+    let dataset = dataset_json(args);
+    for (name, members) in names.zip(partition) {
+        let records = members.iter().map(|&i| &labeled[i].0);
+        if let (Some(base), Some(dataset)) = (&args.output_base, &dataset) {
+            let path = base.with_extension(format!("{name}.json"));
+            write_dataset(&path, dataset, records.clone()).unwrap_or_else(
+                |e| die!("failed to write {:?} with {}", path, e),
+            );
+        }
+        let res = if let Some(base) = &args.output_base {
+            let path = base.with_extension(name);
+            std::fs::File::create(&path)
+                .and_then(|mut f| write_records(&mut f, None, records))
+        } else {
+            write_records(&mut std::io::stdout(), Some(name), records)
+        };
+        res.unwrap_or_else(|e| die!("failed to write group {name} with {e}"));
+    }
+}
+
 mod cli {
     use std::path::PathBuf;
 
@@ -92,11 +227,49 @@ mod cli {
         #[arg(short, long)]
         pub dataset: PathBuf,
 
+        /// The force field to label molecules with. Only required if the
+        /// selector refers to parameter IDs
         #[arg(short, long)]
-        pub forcefield: String,
+        pub forcefield: Option<String>,
 
-        #[arg(short, long)]
-        pub subset: PathBuf,
+        /// A file containing the selector for the in-set. See the `select`
+        /// module for the syntax, but a plain list of parameter IDs selects
+        /// records matching any of them
+        #[arg(short, long, required_unless_present_any = ["expr", "groups"])]
+        pub subset: Option<PathBuf>,
+
+        /// A selector for the in-set given directly on the command line, like
+        /// `"t17>=2 and not /t1[0-9]+/"`
+        #[arg(short, long, conflicts_with = "subset")]
+        pub expr: Option<String>,
+
+        /// A file of `name: selector` lines defining any number of groups to
+        /// split the records into in a single labeling pass, instead of a
+        /// single in-set and out-set. Records matching no group are written to
+        /// an `unassigned` group
+        #[arg(short, long, conflicts_with_all = ["subset", "expr"])]
+        pub groups: Option<PathBuf>,
+
+        /// Treat records matching more than one group as an error
+        #[arg(long, requires = "groups")]
+        pub exclusive: bool,
+
+        /// Print a statistical comparison of the in-set and out-set values to
+        /// stderr
+        #[arg(short, long, conflicts_with = "groups")]
+        pub compare: bool,
+
+        /// Also write the dataset entries for each output set to a new dataset
+        /// JSON file named like the CSV output with an additional `.json`
+        /// extension. Fields not used by ffsubset are preserved
+        #[arg(short, long, requires = "output_base")]
+        pub write_datasets: bool,
+
+        /// Write a JSON Lines file recording the set of each record and the
+        /// selector terms it matched, with the parameter IDs or SMARTS
+        /// patterns that triggered them and their chemical environments
+        #[arg(long)]
+        pub explain: Option<PathBuf>,
 
         #[arg(short, long, default_value_t = 0)]
         pub threads: usize,
@@ -114,33 +287,71 @@ fn main() {
         .build_global()
         .expect("failed to initialize thread pool");
 
-    let Output { in_set, out_set } =
-        inner(args.records, args.dataset, &args.forcefield, args.subset);
-
-    let mut prefix = true;
-    let (mut win, mut wout): (
-        Box<dyn std::io::Write>,
-        Box<dyn std::io::Write>,
-    ) = if let Some(base) = args.output_base {
-        prefix = false;
-        let in_file = std::fs::File::create(base.with_extension("in")).unwrap();
-        let out_file =
-            std::fs::File::create(base.with_extension("out")).unwrap();
-        (Box::new(in_file), Box::new(out_file))
-    } else {
-        (Box::new(std::io::stdout()), Box::new(std::io::stdout()))
+    if let Some(path) = &args.groups {
+        groups_main(&args, path);
+        return;
+    }
+
+    let subset = match (&args.subset, &args.expr) {
+        (_, Some(expr)) => select::parse(expr)
+            .unwrap_or_else(|e| die!("failed to parse {:?} with {}", expr, e)),
+        (Some(path), None) => load_subset(path)
+            .unwrap_or_else(|e| die!("failed to load {:?} with {}", path, e)),
+        (None, None) => unreachable!(),
     };
 
-    for (rec, _) in in_set {
-        if prefix {
-            write!(win, "inset,").unwrap();
-        }
-        writeln!(win, "{},{}", rec.id, rec.value).unwrap();
+    let Output { in_set, out_set } = inner(
+        &args.records,
+        &args.dataset,
+        args.forcefield.as_deref(),
+        &subset,
+    );
+
+    if args.compare {
+        let values = |set: &[(Record, Labels)]| -> Vec<f64> {
+            set.iter().map(|(r, _)| r.value).collect()
+        };
+        compare::write_comparison(
+            &mut std::io::stderr(),
+            &values(&in_set),
+            &values(&out_set),
+        )
+        .unwrap();
     }
-    for (rec, _) in out_set {
-        if prefix {
-            write!(win, "outset,").unwrap();
+
+    if let Some(path) = &args.explain {
+        let sets = [("in", in_set.as_slice()), ("out", out_set.as_slice())];
+        std::fs::File::create(path)
+            .map(std::io::BufWriter::new)
+            .and_then(|mut w| {
+                explain::write_explanations(&mut w, &subset, sets)
+            })
+            .unwrap_or_else(|e| die!("failed to write {:?} with {}", path, e));
+    }
+
+    if let (Some(base), Some(dataset)) =
+        (&args.output_base, dataset_json(&args))
+    {
+        for (ext, set) in [("in.json", &in_set), ("out.json", &out_set)] {
+            let path = base.with_extension(ext);
+            write_dataset(&path, &dataset, set.iter().map(|(r, _)| r))
+                .unwrap_or_else(|e| {
+                    die!("failed to write {:?} with {}", path, e)
+                });
         }
-        writeln!(wout, "{},{}", rec.id, rec.value).unwrap();
+    }
+
+    for (ext, prefix, set) in
+        [("in", "inset", &in_set), ("out", "outset", &out_set)]
+    {
+        let records = set.iter().map(|(r, _)| r);
+        let res = if let Some(base) = &args.output_base {
+            let path = base.with_extension(ext);
+            std::fs::File::create(&path)
+                .and_then(|mut f| write_records(&mut f, None, records))
+        } else {
+            write_records(&mut std::io::stdout(), Some(prefix), records)
+        };
+        res.unwrap_or_else(|e| die!("failed to write {prefix} with {e}"));
     }
 }
@@ -0,0 +1,422 @@
+//! A small expression language for selecting records by their parameters
+//!
+//! A selector is built from terms combined with `and`/`&`, `or`/`|`, and
+//! `not`/`!`, grouped with parentheses. `not` binds tighter than `and`, which
+//! binds tighter than `or`, and terms written next to each other without an
+//! operator are combined with `or`, so a plain whitespace-separated list of
+//! parameter IDs selects records matching any of them. A term is one of
+//!
+//! - a parameter ID like `t17`
+//! - a glob like `t17*`, where `*` matches any sequence of characters and `?`
+//!   matches any single character
+//! - a regular expression between slashes like `/t1[0-9]+/`, which must match
+//!   the whole parameter ID
+//! - a SMARTS pattern prefixed with `smarts:` like `smarts:[#16](=O)(=O)N`,
+//!   which is matched against the molecule directly instead of against its
+//!   parameters. The pattern extends to the next whitespace, `>=`, or `)`
+//!   closing a parenthesis opened outside of it
+//!
+//! and can be followed by `>=N` to require at least `N` chemical environments
+//! assigned to any of the matching parameters, or at least `N` matches of a
+//! SMARTS pattern, as in `t17>=2`.
+
+use std::collections::HashMap;
+
+use fftools::Pid;
+use rdkit_rs::ROMol;
+use regex::Regex;
+
+/// The chemical environments found in a single molecule
+#[derive(Default)]
+pub(crate) struct Labels {
+    /// the environments assigned each parameter ID
+    pub(crate) params: HashMap<Pid, Vec<Vec<usize>>>,
+
+    /// the matches of each SMARTS pattern used in the selector
+    pub(crate) smarts: HashMap<String, Vec<Vec<usize>>>,
+}
+
+/// The parameter IDs or SMARTS pattern matched by a single term
+#[derive(Debug)]
+pub(crate) enum Pattern {
+    Exact(String),
+    Regex(Regex),
+    Smarts(String),
+}
+
+/// The parameter IDs or SMARTS pattern in a molecule matching a term, along
+/// with their chemical environments
+pub(crate) type Matches<'a> = Vec<(&'a str, &'a [Vec<usize>])>;
+
+impl Pattern {
+    /// Return the entries in `labels` matching `self`
+    fn matching<'a>(&self, labels: &'a Labels) -> Matches<'a> {
+        let params = |f: &dyn Fn(&str) -> bool| -> Matches<'a> {
+            let mut ret: Matches = labels
+                .params
+                .iter()
+                .filter(|(pid, _)| f(pid))
+                .map(|(pid, envs)| (pid.as_str(), envs.as_slice()))
+                .collect();
+            ret.sort();
+            ret
+        };
+        match self {
+            Pattern::Exact(s) => params(&|pid| s == pid),
+            Pattern::Regex(re) => params(&|pid| re.is_match(pid)),
+            Pattern::Smarts(s) => labels
+                .smarts
+                .get_key_value(s)
+                .map(|(s, envs)| (s.as_str(), envs.as_slice()))
+                .into_iter()
+                .collect(),
+        }
+    }
+
+    /// Count the environments in `labels` matching `self`
+    fn count(&self, labels: &Labels) -> usize {
+        self.matching(labels)
+            .iter()
+            .map(|(_, envs)| envs.len())
+            .sum()
+    }
+}
+
+/// A term of a selector satisfied by a molecule, as reported by
+/// [Expr::explain]
+pub(crate) struct Explanation<'a> {
+    /// the term as written in the selector
+    pub(crate) term: &'a str,
+
+    /// whether the term appears under a `not`, in which case it counts against
+    /// selecting the molecule
+    pub(crate) negated: bool,
+
+    pub(crate) matches: Matches<'a>,
+}
+
+#[derive(Debug)]
+pub(crate) enum Expr {
+    /// at least `min` environments assigned parameters matching `pattern`,
+    /// written as `text` in the selector
+    Term {
+        text: String,
+        pattern: Pattern,
+        min: usize,
+    },
+    Not(Box<Expr>),
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+}
+
+impl Expr {
+    /// Report whether the molecule labeled with `labels` is selected by `self`
+    pub(crate) fn matches(&self, labels: &Labels) -> bool {
+        match self {
+            Expr::Term { pattern, min, .. } => pattern.count(labels) >= *min,
+            Expr::Not(e) => !e.matches(labels),
+            Expr::And(es) => es.iter().all(|e| e.matches(labels)),
+            Expr::Or(es) => es.iter().any(|e| e.matches(labels)),
+        }
+    }
+
+    /// Return every term in `self` satisfied by the molecule labeled with
+    /// `labels`, along with the entries in `labels` that satisfied it
+    pub(crate) fn explain<'a>(
+        &'a self,
+        labels: &'a Labels,
+    ) -> Vec<Explanation<'a>> {
+        let mut ret = Vec::new();
+        self.explain_inner(labels, false, &mut ret);
+        ret
+    }
+
+    fn explain_inner<'a>(
+        &'a self,
+        labels: &'a Labels,
+        negated: bool,
+        ret: &mut Vec<Explanation<'a>>,
+    ) {
+        match self {
+            Expr::Term { text, pattern, min } => {
+                let matches = pattern.matching(labels);
+                let count: usize = matches.iter().map(|(_, e)| e.len()).sum();
+                if count >= *min {
+                    ret.push(Explanation {
+                        term: text,
+                        negated,
+                        matches,
+                    });
+                }
+            }
+            Expr::Not(e) => e.explain_inner(labels, !negated, ret),
+            Expr::And(es) | Expr::Or(es) => {
+                for e in es {
+                    e.explain_inner(labels, negated, ret);
+                }
+            }
+        }
+    }
+
+    /// Return the SMARTS patterns used anywhere in `self`
+    pub(crate) fn smarts(&self) -> Vec<&str> {
+        match self {
+            Expr::Term {
+                pattern: Pattern::Smarts(s),
+                ..
+            } => vec![s.as_str()],
+            Expr::Term { .. } => Vec::new(),
+            Expr::Not(e) => e.smarts(),
+            Expr::And(es) | Expr::Or(es) => {
+                es.iter().flat_map(Expr::smarts).collect()
+            }
+        }
+    }
+
+    /// Report whether any term in `self` refers to parameter IDs, meaning that
+    /// molecules have to be labeled with a force field to evaluate it
+    pub(crate) fn uses_params(&self) -> bool {
+        match self {
+            Expr::Term {
+                pattern: Pattern::Smarts(_),
+                ..
+            } => false,
+            Expr::Term { .. } => true,
+            Expr::Not(e) => e.uses_params(),
+            Expr::And(es) | Expr::Or(es) => es.iter().any(Expr::uses_params),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    LParen,
+    RParen,
+    And,
+    Or,
+    Not,
+    AtLeast(usize),
+    Word(String),
+    Regex(String),
+    Smarts(String),
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, String> {
+    let mut ret = Vec::new();
+    let mut chars = s.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' | ')' | '&' | '|' | '!' => {
+                chars.next();
+                ret.push(match c {
+                    '(' => Token::LParen,
+                    ')' => Token::RParen,
+                    '&' => Token::And,
+                    '|' => Token::Or,
+                    _ => Token::Not,
+                });
+            }
+            '/' => {
+                chars.next();
+                let mut re = String::new();
+                loop {
+                    match chars.next() {
+                        Some('/') => break,
+                        Some('\\') if chars.peek() == Some(&'/') => {
+                            re.push(chars.next().unwrap());
+                        }
+                        Some(c) => re.push(c),
+                        None => {
+                            return Err(format!("unterminated regex /{re}"))
+                        }
+                    }
+                }
+                ret.push(Token::Regex(re));
+            }
+            '>' => {
+                chars.next();
+                if chars.next() != Some('=') {
+                    return Err("expected `>=`".to_owned());
+                }
+                let mut n = String::new();
+                while let Some(d) = chars.next_if(char::is_ascii_digit) {
+                    n.push(d);
+                }
+                let n = n
+                    .parse()
+                    .map_err(|_| "expected a count after `>=`".to_owned())?;
+                ret.push(Token::AtLeast(n));
+            }
+            _ if chars.clone().take(7).eq("smarts:".chars()) => {
+                chars.nth(6);
+                // the branches opened in the pattern so far
+                let mut depth = 0;
+                let mut smarts = String::new();
+                while let Some(&c) = chars.peek() {
+                    match c {
+                        '(' => depth += 1,
+                        ')' if depth == 0 => break,
+                        ')' => depth -= 1,
+                        '>' if chars.clone().nth(1) == Some('=') => break,
+                        c if c.is_whitespace() => break,
+                        _ => {}
+                    }
+                    smarts.push(c);
+                    chars.next();
+                }
+                if smarts.is_empty() {
+                    return Err("expected a pattern after `smarts:`".to_owned());
+                }
+                ret.push(Token::Smarts(smarts));
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(c) = chars
+                    .next_if(|c| !c.is_whitespace() && !"()&|!>".contains(*c))
+                {
+                    word.push(c);
+                }
+                ret.push(match word.as_str() {
+                    "and" => Token::And,
+                    "or" => Token::Or,
+                    "not" => Token::Not,
+                    _ => Token::Word(word),
+                });
+            }
+        }
+    }
+    Ok(ret)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<&Token> {
+        self.pos += 1;
+        self.tokens.get(self.pos - 1)
+    }
+
+    fn starts_operand(&self) -> bool {
+        matches!(
+            self.peek(),
+            Some(
+                Token::LParen
+                    | Token::Not
+                    | Token::Word(_)
+                    | Token::Regex(_)
+                    | Token::Smarts(_)
+            )
+        )
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut es = vec![self.and()?];
+        loop {
+            if self.peek() == Some(&Token::Or) {
+                self.next();
+            } else if !self.starts_operand() {
+                break;
+            }
+            es.push(self.and()?);
+        }
+        Ok(if es.len() == 1 {
+            es.pop().unwrap()
+        } else {
+            Expr::Or(es)
+        })
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut es = vec![self.unary()?];
+        while self.peek() == Some(&Token::And) {
+            self.next();
+            es.push(self.unary()?);
+        }
+        Ok(if es.len() == 1 {
+            es.pop().unwrap()
+        } else {
+            Expr::And(es)
+        })
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.peek() == Some(&Token::Not) {
+            self.next();
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        let mut text = match self.peek() {
+            Some(Token::Word(w)) => w.clone(),
+            Some(Token::Regex(re)) => format!("/{re}/"),
+            Some(Token::Smarts(s)) => format!("smarts:{s}"),
+            _ => String::new(),
+        };
+        let pattern = match self.next() {
+            Some(Token::LParen) => {
+                let e = self.or()?;
+                if self.next() != Some(&Token::RParen) {
+                    return Err("expected `)`".to_owned());
+                }
+                return Ok(e);
+            }
+            Some(Token::Word(w)) if w.contains(['*', '?']) => {
+                let re =
+                    regex::escape(w).replace("\\*", ".*").replace("\\?", ".");
+                Pattern::Regex(anchored(&re)?)
+            }
+            Some(Token::Word(w)) => Pattern::Exact(w.clone()),
+            Some(Token::Regex(re)) => Pattern::Regex(anchored(re)?),
+            Some(Token::Smarts(s)) => {
+                if let Err(e) = ROMol::try_from_smarts(s) {
+                    return Err(format!("invalid SMARTS `{s}`: {e}"));
+                }
+                Pattern::Smarts(s.clone())
+            }
+            Some(t) => return Err(format!("unexpected {t:?}")),
+            None => return Err("unexpected end of selector".to_owned()),
+        };
+        let min = match self.peek() {
+            Some(&Token::AtLeast(n)) => {
+                self.next();
+                text.push_str(&format!(" >={n}"));
+                n
+            }
+            _ => 1,
+        };
+        Ok(Expr::Term { text, pattern, min })
+    }
+}
+
+/// Compile `re` so that it has to match an entire parameter ID
+fn anchored(re: &str) -> Result<Regex, String> {
+    Regex::new(&format!("^(?:{re})$")).map_err(|e| e.to_string())
+}
+
+/// Parse a selector [Expr] from `s`
+pub(crate) fn parse(s: &str) -> Result<Expr, String> {
+    let mut parser = Parser {
+        tokens: tokenize(s)?,
+        pos: 0,
+    };
+    if parser.peek().is_none() {
+        return Err("empty selector".to_owned());
+    }
+    let e = parser.or()?;
+    if let Some(t) = parser.peek() {
+        return Err(format!("unexpected {t:?}"));
+    }
+    Ok(e)
+}
@@ -5,10 +5,130 @@ fn test_inner() {
     let Output { in_set, out_set } = inner(
         "../testfiles/dde.csv",
         "../testfiles/industry.json",
-        "openff-2.1.0.offxml",
-        "../testfiles/subset.in",
+        Some("openff-2.1.0.offxml"),
+        &load_subset("../testfiles/subset.in").unwrap(),
     );
 
     assert_eq!(in_set.len(), 58825);
     assert_eq!(out_set.len(), 12935);
 }
+
+fn labels(pairs: &[(&str, usize)]) -> Labels {
+    let mut ret = Labels::default();
+    for &(key, n) in pairs {
+        let envs = vec![vec![0, 1, 2, 3]; n];
+        match key.strip_prefix("smarts:") {
+            Some(smarts) => ret.smarts.insert(smarts.to_owned(), envs),
+            None => ret.params.insert(key.to_owned(), envs),
+        };
+    }
+    ret
+}
+
+#[test]
+fn test_select() {
+    let mol = labels(&[
+        ("t17", 2),
+        ("t18", 1),
+        ("t105", 1),
+        ("smarts:[#16](=O)(=O)N", 1),
+        ("smarts:[#6:1]-[#6:2]", 4),
+    ]);
+    let tests = [
+        ("t1 t2 t17", true),
+        ("t1 | t2", false),
+        ("t17 & t18", true),
+        ("t17 and t19", false),
+        ("t17 & !t105", false),
+        ("t1 or t17 and not t18", false),
+        ("(t1 or t17) and t18", true),
+        ("t17>=2", true),
+        ("t18>=2", false),
+        ("t1*>=3", true),
+        ("t1?", true),
+        ("/t1[0-9]{2}/", true),
+        ("/t1[0-9]/", true),
+        ("/t[0-9]/", false),
+        ("smarts:[#16](=O)(=O)N", true),
+        ("smarts:[#16](=O)(=O)N and !t18", false),
+        ("(smarts:[#6:1]-[#6:2] >=4 )", true),
+        ("smarts:[#6:1]-[#6:2] >=5", false),
+        ("smarts:[#7]", false),
+        ("/smarts:.*/", false),
+    ];
+    for (input, want) in tests {
+        let expr = select::parse(input).unwrap();
+        assert_eq!(expr.matches(&mol), want, "{input}");
+    }
+
+    let expr = select::parse("t17 | smarts:[#7] & !smarts:[#8]").unwrap();
+    assert_eq!(expr.smarts(), ["[#7]", "[#8]"]);
+    assert!(expr.uses_params());
+    assert!(!select::parse("smarts:[#7]").unwrap().uses_params());
+
+    let expr = select::parse("t1* >=2 & !t105 | smarts:[#7]").unwrap();
+    let explained = expr.explain(&mol);
+    assert_eq!(explained.len(), 2);
+    assert_eq!(explained[0].term, "t1* >=2");
+    assert!(!explained[0].negated);
+    let pids: Vec<_> = explained[0].matches.iter().map(|(p, _)| *p).collect();
+    assert_eq!(pids, ["t105", "t17", "t18"]);
+    assert_eq!(explained[1].term, "t105");
+    assert!(explained[1].negated);
+
+    for bad in ["", "t17 and", "(t17", "t17>=", "/t17", "/t[/", "smarts: t1"] {
+        assert!(select::parse(bad).is_err(), "{bad}");
+    }
+}
+
+#[test]
+fn test_parse_smarts() {
+    // the pattern ends at a parenthesis it didn't open or a trailing count
+    let expr = select::parse("(t17 | smarts:[#16])").unwrap();
+    assert_eq!(expr.smarts(), ["[#16]"]);
+    let expr = select::parse("(smarts:[#16](=O)(=O)N)").unwrap();
+    assert_eq!(expr.smarts(), ["[#16](=O)(=O)N"]);
+    let expr = select::parse("smarts:[#7]>=2").unwrap();
+    assert_eq!(expr.smarts(), ["[#7]"]);
+    assert!(matches!(expr, Expr::Term { min: 2, .. }));
+
+    for bad in ["smarts:[#16", "smarts:[#7]]", "(smarts:C(C)"] {
+        assert!(select::parse(bad).is_err(), "{bad}");
+    }
+}
+
+#[test]
+fn test_groups() {
+    let dir = std::env::temp_dir()
+        .join(format!("ffsubset_test_groups_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("groups.in");
+    std::fs::write(
+        &path,
+        "# torsion families\nt17: t17 | t18\n\nsulfonamide: smarts:[#16]N\n",
+    )
+    .unwrap();
+    let groups = load_groups(&path).unwrap();
+    assert_eq!(groups.len(), 2);
+    assert_eq!(groups[0].name, "t17");
+
+    let labeled: Vec<_> = [
+        labels(&[("t17", 1)]),
+        labels(&[("t18", 1), ("smarts:[#16]N", 1)]),
+        labels(&[("t1", 1)]),
+    ]
+    .into_iter()
+    .enumerate()
+    .map(|(id, labels)| (Record { id, value: 0.0 }, labels))
+    .collect();
+
+    let got = groups::partition(&labeled, &groups, false).unwrap();
+    assert_eq!(got, vec![vec![0, 1], vec![1], vec![2]]);
+    assert!(groups::partition(&labeled, &groups, true).is_err());
+
+    for bad in ["a: t1\nunassigned: t2\n", "a/b: t1\n", "x.json: t1\n"] {
+        std::fs::write(&path, bad).unwrap();
+        assert!(load_groups(&path).is_err(), "{bad}");
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
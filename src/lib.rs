@@ -1,5 +1,10 @@
 use serde::Deserialize;
-use std::{collections::HashMap, fs::read_to_string, io, path::Path};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::read_to_string,
+    io,
+    path::Path,
+};
 
 /// A parameter identifier
 pub type Pid = String;
@@ -8,6 +13,10 @@ pub type Pid = String;
 pub type Smiles = String;
 
 pub mod parameter_map;
+pub mod stats;
+
+#[cfg(test)]
+mod tests;
 
 #[macro_export]
 macro_rules! die {
@@ -44,6 +53,61 @@ pub fn load_csv(path: impl AsRef<Path>) -> io::Result<Vec<Record>> {
         .collect())
 }
 
+/// A CSV file of record IDs followed by any number of value columns, as loaded
+/// by [load_table]
+pub struct Table {
+    /// the names of the value columns, taken from the header
+    pub columns: Vec<String>,
+
+    /// the QCArchive record ID and the value in each column for every row.
+    /// empty or `nan` cells are `None`
+    pub rows: Vec<(usize, Vec<Option<f64>>)>,
+}
+
+/// Load a CSV file like those accepted by [load_csv] but with any number of
+/// value columns from `path`. The first line is the header, and the first
+/// column must hold the record IDs.
+pub fn load_table(path: impl AsRef<Path>) -> io::Result<Table> {
+    let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+    let s = read_to_string(path)?;
+    let mut lines = s.lines();
+    let header = lines
+        .next()
+        .ok_or_else(|| invalid("empty CSV file".to_owned()))?;
+    let columns: Vec<_> = header
+        .split(',')
+        .skip(1)
+        .map(|s| s.trim().to_owned())
+        .collect();
+    let mut rows = Vec::new();
+    for line in lines.filter(|l| !l.trim().is_empty()) {
+        let sp: Vec<_> = line.split(',').map(str::trim).collect();
+        if sp.len() != columns.len() + 1 {
+            return Err(invalid(format!(
+                "expected {} columns, found {} in `{line}`",
+                columns.len() + 1,
+                sp.len()
+            )));
+        }
+        let id = sp[0]
+            .parse()
+            .map_err(|e| invalid(format!("bad record ID `{}`: {e}", sp[0])))?;
+        let mut values = Vec::with_capacity(columns.len());
+        for v in &sp[1..] {
+            let v: Option<f64> =
+                match *v {
+                    "" => None,
+                    v => Some(v.parse().map_err(|e| {
+                        invalid(format!("bad value `{v}`: {e}"))
+                    })?),
+                };
+            values.push(v.filter(|v| !v.is_nan()));
+        }
+        rows.push((id, values));
+    }
+    Ok(Table { columns, rows })
+}
+
 /// A single entry in a [Dataset]
 #[derive(Deserialize)]
 pub struct Entry<T> {
@@ -66,13 +130,21 @@ pub struct Dataset<T> {
 /// OptimizationResultCollections and TorsionDriveResultCollections.
 pub fn load_dataset(
     path: impl AsRef<Path>,
+) -> io::Result<HashMap<String, String>> {
+    dataset_smiles(&load_dataset_json(path)?)
+}
+
+/// Return the map of record ID to SMILES in `dataset`, as loaded by
+/// [load_dataset_json], like [load_dataset] does for a file.
+pub fn dataset_smiles(
+    dataset: &serde_json::Value,
 ) -> io::Result<HashMap<String, String>> {
     /// new datasets keep the record IDs as integers, not strings. if loading as
     /// a string fails, try again with usize
     fn inner<T: for<'a> Deserialize<'a> + Eq + std::hash::Hash>(
-        path: impl AsRef<Path>,
+        dataset: &serde_json::Value,
     ) -> io::Result<HashMap<T, String>> {
-        let ds: Dataset<T> = serde_json::from_str(&read_to_string(path)?)?;
+        let ds = Dataset::<T>::deserialize(dataset)?;
         Ok(ds
             .entries
             .into_values()
@@ -80,12 +152,49 @@ pub fn load_dataset(
             .map(|rec| (rec.record_id, rec.cmiles))
             .collect())
     }
-    let ret = match inner::<String>(&path) {
+    let ret = match inner::<String>(dataset) {
         Ok(ds) => ds,
         Err(_) => {
-            let ds = inner::<usize>(&path)?;
+            let ds = inner::<usize>(dataset)?;
             ds.into_iter().map(|(k, v)| (k.to_string(), v)).collect()
         }
     };
     Ok(ret)
 }
+
+/// Load the raw JSON of the dataset at `path` for use with [filter_dataset]
+pub fn load_dataset_json(
+    path: impl AsRef<Path>,
+) -> io::Result<serde_json::Value> {
+    Ok(serde_json::from_str(&read_to_string(path)?)?)
+}
+
+/// Return a copy of `dataset`, as loaded by [load_dataset_json], containing
+/// only the entries whose record IDs are in `keep`.
+///
+/// Like [load_dataset], this works directly on the JSON, so all of the fields
+/// and the nesting of `entries` are preserved, and the result can be written
+/// back out as a dataset that QCSubmit can load. Record IDs can be either
+/// strings or integers in the JSON, but they are compared as strings.
+pub fn filter_dataset(
+    dataset: &serde_json::Value,
+    keep: &HashSet<String>,
+) -> serde_json::Value {
+    use serde_json::Value;
+    let mut ret = dataset.clone();
+    let Some(entries) = ret.get_mut("entries").and_then(Value::as_object_mut)
+    else {
+        return ret;
+    };
+    for entry in entries.values_mut() {
+        let Some(entry) = entry.as_array_mut() else {
+            continue;
+        };
+        entry.retain(|e| match e.get("record_id") {
+            Some(Value::String(s)) => keep.contains(s),
+            Some(Value::Number(n)) => keep.contains(&n.to_string()),
+            _ => false,
+        });
+    }
+    ret
+}
@@ -1,21 +1,55 @@
 use log::trace;
-use openff_toolkit::typing::engines::smirnoff::parameters::ParameterHandler;
+use openff_toolkit::ForceField;
 use rdkit_rs::{find_smarts_matches_mol, ROMol};
 
 use std::collections::HashMap;
 
 use crate::Pid;
 
+#[cfg(test)]
+mod tests;
+
 /// A [ParameterMap] is basically the Rust/RDKit-compatible version of an OpenFF
 /// ForceField. Instead of having to access parameter SMIRKS patterns as Strings
 /// and pass them to RDKit as such, a [ParameterMap] converts them all to
 /// [ROMol] up front for faster matching in [ParameterMap::label_molecule]. TODO
-/// support more than one [ParameterHandler] at a time.
-pub struct ParameterMap(Vec<(Pid, ROMol)>);
+/// support more than one parameter handler at a time.
+pub struct ParameterMap {
+    params: Vec<(Pid, String, ROMol)>,
+    order: KeyOrder,
+}
 
 impl ParameterMap {
+    /// build a [ParameterMap] from parameter IDs and SMIRKS patterns in
+    /// hierarchy order, keying the environments they match in `order`
+    pub fn new(
+        order: KeyOrder,
+        params: impl IntoIterator<Item = (Pid, String)>,
+    ) -> Self {
+        let params = params
+            .into_iter()
+            .map(|(id, smirks)| {
+                let mol = ROMol::from_smarts(&smirks);
+                (id, smirks, mol)
+            })
+            .collect();
+        Self { params, order }
+    }
+
+    /// build a [ParameterMap] from the parameters of `handler` in
+    /// `forcefield`, keyed in the order for that handler, if the force field
+    /// has it
+    pub fn from_handler(
+        forcefield: &ForceField,
+        handler: &str,
+    ) -> Option<Self> {
+        let ph = forcefield.get_parameter_handler(handler)?;
+        let params = ph.parameters().into_iter().map(|p| (p.id(), p.smirks()));
+        Some(Self::new(KeyOrder::for_handler(handler), params))
+    }
+
     pub fn len(&self) -> usize {
-        self.0.len()
+        self.params.len()
     }
 
     #[must_use]
@@ -24,34 +58,175 @@ impl ParameterMap {
     }
 
     pub fn keys(&self) -> impl Iterator<Item = &Pid> {
-        self.0.iter().map(|(pid, _mol)| pid)
+        self.params.iter().map(|(pid, _smirks, _mol)| pid)
+    }
+
+    /// return the parameter IDs paired with their SMIRKS patterns, in the same
+    /// order as [ParameterMap::keys]
+    pub fn smirks(&self) -> impl Iterator<Item = (&Pid, &str)> {
+        self.params
+            .iter()
+            .map(|(pid, smirks, _mol)| (pid, smirks.as_str()))
     }
 
     /// label `mol` with `params` and return a map of chemical environment
     /// tuples to parameter IDs
     pub fn label_molecule(&self, mol: &ROMol) -> HashMap<Vec<usize>, String> {
         let mut matches = HashMap::new();
-        for (id, smirks) in &self.0 {
-            let env_matches = find_smarts_matches_mol(mol, smirks);
-            for mut mat in env_matches {
-                if mat.first().unwrap() > mat.last().unwrap() {
-                    mat.reverse();
+        for (id, _smirks, pattern) in &self.params {
+            let env_matches = find_smarts_matches_mol(mol, pattern);
+            assign(&mut matches, id, env_matches, self.order);
+        }
+        matches
+    }
+}
+
+/// How the atoms of a chemical environment are ordered to give each
+/// environment a single key, regardless of the order it was matched in
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum KeyOrder {
+    /// reversed if the first atom index is greater than the last, as for
+    /// bonds, angles, and proper torsions
+    #[default]
+    Reversible,
+
+    /// the outer atoms sorted around the central second atom, as for improper
+    /// torsions, whose SMIRKS match each improper in several orders
+    Improper,
+}
+
+impl KeyOrder {
+    /// the key order for the parameters of `handler`
+    pub fn for_handler(handler: &str) -> Self {
+        match handler {
+            "ImproperTorsions" => Self::Improper,
+            _ => Self::Reversible,
+        }
+    }
+
+    /// put `env` into this order
+    fn canonicalize(self, env: &mut [usize]) {
+        match self {
+            Self::Reversible => {
+                if env.first().unwrap() > env.last().unwrap() {
+                    env.reverse();
                 }
-                trace!("{mat:?} => {id}");
-                matches.insert(mat, id.clone());
+            }
+            Self::Improper => {
+                let center = env[1];
+                let mut outer = [env[0], env[2], env[3]];
+                outer.sort();
+                env.copy_from_slice(&[outer[0], center, outer[1], outer[2]]);
             }
         }
-        matches
     }
 }
 
-impl From<ParameterHandler> for ParameterMap {
-    fn from(ph: ParameterHandler) -> Self {
-        Self(
-            ph.parameters()
-                .into_iter()
-                .map(|p| (p.id(), ROMol::from_smarts(&p.smirks())))
-                .collect(),
-        )
+/// assign `id` to each of the chemical environments in `env_matches`, put into
+/// `order`, overwriting any earlier assignment, as in the SMIRNOFF hierarchy
+fn assign(
+    matches: &mut HashMap<Vec<usize>, String>,
+    id: &Pid,
+    env_matches: impl IntoIterator<Item = Vec<usize>>,
+    order: KeyOrder,
+) {
+    for mut mat in env_matches {
+        order.canonicalize(&mut mat);
+        trace!("{mat:?} => {id}");
+        matches.insert(mat, id.clone());
+    }
+}
+
+/// Several force fields' versions of a single handler, sharing their SMIRKS
+/// patterns. Each unique pattern is matched only once per molecule in
+/// [MultiParameterMap::label_molecule], so comparing related force fields
+/// costs little more than labeling with one of them.
+pub struct MultiParameterMap {
+    /// the unique SMIRKS patterns, in order of first appearance
+    patterns: Vec<(String, ROMol)>,
+
+    /// the parameter IDs of each force field in order, with the index of their
+    /// pattern
+    forcefields: Vec<Vec<(Pid, usize)>>,
+
+    order: KeyOrder,
+}
+
+impl MultiParameterMap {
+    /// build a [MultiParameterMap] from the parameter IDs and SMIRKS patterns
+    /// of each force field, in hierarchy order, keying the environments they
+    /// match in `order`
+    pub fn new<F, P>(order: KeyOrder, forcefields: F) -> Self
+    where
+        F: IntoIterator<Item = P>,
+        P: IntoIterator<Item = (Pid, String)>,
+    {
+        let mut index: HashMap<String, usize> = HashMap::new();
+        let mut patterns = Vec::new();
+        let forcefields = forcefields
+            .into_iter()
+            .map(|params| {
+                params
+                    .into_iter()
+                    .map(|(id, smirks)| {
+                        let i = *index.entry(smirks).or_insert_with_key(|s| {
+                            patterns.push((s.clone(), ROMol::from_smarts(s)));
+                            patterns.len() - 1
+                        });
+                        (id, i)
+                    })
+                    .collect()
+            })
+            .collect();
+        Self {
+            patterns,
+            forcefields,
+            order,
+        }
+    }
+
+    /// the number of force fields
+    pub fn len(&self) -> usize {
+        self.forcefields.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// the number of unique SMIRKS patterns across all of the force fields
+    pub fn unique_patterns(&self) -> usize {
+        self.patterns.len()
+    }
+
+    /// the parameter IDs of force field `ff`, in hierarchy order
+    pub fn keys(&self, ff: usize) -> impl Iterator<Item = &Pid> {
+        self.forcefields[ff].iter().map(|(pid, _)| pid)
+    }
+
+    /// label `mol` with each of the force fields and return a map of chemical
+    /// environment tuples to parameter IDs for each one, identical to those
+    /// from [ParameterMap::label_molecule]
+    pub fn label_molecule(
+        &self,
+        mol: &ROMol,
+    ) -> Vec<HashMap<Vec<usize>, String>> {
+        let env_matches: Vec<_> = self
+            .patterns
+            .iter()
+            .map(|(_smirks, pattern)| find_smarts_matches_mol(mol, pattern))
+            .collect();
+        self.forcefields
+            .iter()
+            .map(|params| {
+                let mut matches = HashMap::new();
+                for (id, i) in params {
+                    let envs = env_matches[*i].iter().cloned();
+                    assign(&mut matches, id, envs, self.order);
+                }
+                matches
+            })
+            .collect()
     }
 }
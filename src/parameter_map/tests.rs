@@ -0,0 +1,52 @@
+use super::*;
+
+#[test]
+fn test_multi_parameter_map() {
+    let params = |smirks: &[&str]| -> Vec<(Pid, String)> {
+        smirks
+            .iter()
+            .enumerate()
+            .map(|(i, s)| (format!("b{}", i + 1), s.to_string()))
+            .collect()
+    };
+    let old = params(&["[#6:1]-[#6:2]", "[#6:1]-[#8:2]"]);
+    let new = params(&["[#6:1]-[#6:2]", "[#6:1]-[#8:2]", "[#6X4:1]-[#8:2]"]);
+    let multi = MultiParameterMap::new(
+        KeyOrder::Reversible,
+        [old.clone(), new.clone()],
+    );
+    assert_eq!(multi.len(), 2);
+    assert_eq!(multi.unique_patterns(), 3);
+    assert_eq!(multi.keys(1).collect::<Vec<_>>(), ["b1", "b2", "b3"]);
+
+    let mut mol = ROMol::from_smiles("CCO");
+    mol.openff_clean();
+    let got = multi.label_molecule(&mol);
+    let want: Vec<_> = [old, new]
+        .into_iter()
+        .map(|p| {
+            ParameterMap::new(KeyOrder::Reversible, p).label_molecule(&mol)
+        })
+        .collect();
+    assert_eq!(got, want);
+}
+
+#[test]
+fn test_key_order() {
+    let key = |order: KeyOrder, env: &[usize]| {
+        let mut env = env.to_vec();
+        order.canonicalize(&mut env);
+        env
+    };
+    assert_eq!(key(KeyOrder::Reversible, &[3, 1, 0]), [0, 1, 3]);
+    assert_eq!(key(KeyOrder::Reversible, &[0, 2, 1, 3]), [0, 2, 1, 3]);
+    // every order of the outer atoms of one improper gives the same key
+    let improper = KeyOrder::for_handler("ImproperTorsions");
+    for env in [[0, 1, 2, 3], [2, 1, 3, 0], [3, 1, 0, 2]] {
+        assert_eq!(key(improper, &env), [0, 1, 2, 3]);
+    }
+    assert_eq!(
+        KeyOrder::for_handler("ProperTorsions"),
+        KeyOrder::Reversible
+    );
+}
@@ -0,0 +1,243 @@
+//! Summary statistics and significance tests shared by the individual tools
+
+#[cfg(test)]
+mod tests;
+
+pub fn mean(v: &[f64]) -> f64 {
+    v.iter().sum::<f64>() / v.len() as f64
+}
+
+/// The mean of `values` weighted by the corresponding entries in `weights`
+pub fn weighted_mean(values: &[f64], weights: &[f64]) -> f64 {
+    let total: f64 = weights.iter().sum();
+    values.iter().zip(weights).map(|(v, w)| v * w).sum::<f64>() / total
+}
+
+/// The root-mean-square of `values` weighted by the corresponding entries in
+/// `weights`
+pub fn weighted_rms(values: &[f64], weights: &[f64]) -> f64 {
+    let total: f64 = weights.iter().sum();
+    let sq: f64 = values.iter().zip(weights).map(|(v, w)| v * v * w).sum();
+    (sq / total).sqrt()
+}
+
+/// Natural logarithms of `0!` through `n!`, built up front so that binomial
+/// coefficients of dataset-sized numbers don't overflow
+pub struct LnFactorials(Vec<f64>);
+
+impl LnFactorials {
+    pub fn new(n: usize) -> Self {
+        let mut ret = Vec::with_capacity(n + 1);
+        ret.push(0.0);
+        for i in 1..=n {
+            ret.push(ret[i - 1] + (i as f64).ln());
+        }
+        Self(ret)
+    }
+
+    /// ln(n choose k)
+    fn ln_choose(&self, n: usize, k: usize) -> f64 {
+        self.0[n] - self.0[k] - self.0[n - k]
+    }
+
+    /// The upper tail of the hypergeometric distribution, P(X >= `k`), for
+    /// `draws` draws without replacement from a population of size `total`
+    /// containing `successes` successes. This is also the p-value of a
+    /// one-sided Fisher exact test for over-representation.
+    pub fn hypergeometric_sf(
+        &self,
+        k: usize,
+        total: usize,
+        successes: usize,
+        draws: usize,
+    ) -> f64 {
+        let lo = k.max((draws + successes).saturating_sub(total));
+        let hi = successes.min(draws);
+        if lo > hi {
+            return 0.0;
+        }
+        let denom = self.ln_choose(total, draws);
+        let p: f64 = (lo..=hi)
+            .map(|i| {
+                (self.ln_choose(successes, i)
+                    + self.ln_choose(total - successes, draws - i)
+                    - denom)
+                    .exp()
+            })
+            .sum();
+        p.min(1.0)
+    }
+}
+
+/// The odds ratio of the 2x2 contingency table
+///
+/// ```text
+///          | case | control
+/// exposed  |  a   |   b
+/// ---------+------+--------
+/// other    |  c   |   d
+/// ```
+///
+/// with 0.5 added to every cell if any of them is zero
+pub fn odds_ratio(a: usize, b: usize, c: usize, d: usize) -> f64 {
+    let [a, b, c, d] = if a == 0 || b == 0 || c == 0 || d == 0 {
+        [a, b, c, d].map(|x| x as f64 + 0.5)
+    } else {
+        [a, b, c, d].map(|x| x as f64)
+    };
+    (a * d) / (b * c)
+}
+
+/// Apply the Benjamini-Hochberg false discovery rate correction to `pvalues`,
+/// returning the adjusted p-values in the original order
+pub fn benjamini_hochberg(pvalues: &[f64]) -> Vec<f64> {
+    let m = pvalues.len();
+    let mut order: Vec<_> = (0..m).collect();
+    order.sort_by(|&a, &b| pvalues[a].total_cmp(&pvalues[b]));
+    let mut ret = vec![0.0; m];
+    let mut running_min = 1.0_f64;
+    for (rank, &i) in order.iter().enumerate().rev() {
+        let adj = pvalues[i] * m as f64 / (rank + 1) as f64;
+        running_min = running_min.min(adj);
+        ret[i] = running_min;
+    }
+    ret
+}
+
+/// The median of `v`
+pub fn median(v: &[f64]) -> f64 {
+    let mut v = v.to_vec();
+    v.sort_by(f64::total_cmp);
+    let n = v.len();
+    if n % 2 == 1 {
+        v[n / 2]
+    } else {
+        (v[n / 2 - 1] + v[n / 2]) / 2.0
+    }
+}
+
+/// The root-mean-square of `v`
+pub fn rms(v: &[f64]) -> f64 {
+    (v.iter().map(|x| x * x).sum::<f64>() / v.len() as f64).sqrt()
+}
+
+/// The sample standard deviation of `v`
+pub fn std_dev(v: &[f64]) -> f64 {
+    let m = mean(v);
+    let ss: f64 = v.iter().map(|x| (x - m).powi(2)).sum();
+    (ss / (v.len() - 1) as f64).sqrt()
+}
+
+/// The complementary error function, using the Chebyshev approximation from
+/// Numerical Recipes with a fractional error below 1.2e-7
+pub fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let poly = -z * z - 1.26551223
+        + t * (1.00002368
+            + t * (0.37409196
+                + t * (0.09678418
+                    + t * (-0.18628806
+                        + t * (0.27886807
+                            + t * (-1.13520398
+                                + t * (1.48851587
+                                    + t * (-0.82215223 + t * 0.17087277))))))));
+    let ans = t * poly.exp();
+    if x >= 0.0 {
+        ans
+    } else {
+        2.0 - ans
+    }
+}
+
+/// The two-sample Kolmogorov-Smirnov statistic D for `a` and `b`, along with
+/// its asymptotic two-sided p-value
+pub fn ks_2samp(a: &[f64], b: &[f64]) -> (f64, f64) {
+    let mut a = a.to_vec();
+    let mut b = b.to_vec();
+    a.sort_by(f64::total_cmp);
+    b.sort_by(f64::total_cmp);
+    let (n, m) = (a.len() as f64, b.len() as f64);
+    let (mut i, mut j) = (0, 0);
+    let mut d = 0.0_f64;
+    while i < a.len() && j < b.len() {
+        let x = a[i].min(b[j]);
+        while i < a.len() && a[i] <= x {
+            i += 1;
+        }
+        while j < b.len() && b[j] <= x {
+            j += 1;
+        }
+        d = d.max((i as f64 / n - j as f64 / m).abs());
+    }
+
+    let ne = (n * m / (n + m)).sqrt();
+    let lambda = (ne + 0.12 + 0.11 / ne) * d;
+    let mut p = 0.0;
+    let mut sign = 1.0;
+    for k in 1..=100 {
+        let term = sign * (-2.0 * (k * k) as f64 * lambda * lambda).exp();
+        p += term;
+        if term.abs() < 1e-12 {
+            break;
+        }
+        sign = -sign;
+    }
+    (d, (2.0 * p).clamp(0.0, 1.0))
+}
+
+/// The Mann-Whitney U statistic of `a` relative to `b`, along with the
+/// two-sided p-value from the tie-corrected normal approximation with a
+/// continuity correction
+pub fn mann_whitney_u(a: &[f64], b: &[f64]) -> (f64, f64) {
+    let mut all: Vec<(f64, bool)> = a
+        .iter()
+        .map(|&x| (x, true))
+        .chain(b.iter().map(|&x| (x, false)))
+        .collect();
+    all.sort_by(|x, y| x.0.total_cmp(&y.0));
+
+    // assign average ranks to ties, accumulating the rank sum of a and the tie
+    // correction term
+    let mut rank_sum = 0.0;
+    let mut ties = 0.0;
+    let mut i = 0;
+    while i < all.len() {
+        let mut j = i;
+        while j < all.len() && all[j].0 == all[i].0 {
+            j += 1;
+        }
+        let avg = (i + j + 1) as f64 / 2.0;
+        rank_sum += avg * all[i..j].iter().filter(|x| x.1).count() as f64;
+        let t = (j - i) as f64;
+        ties += t * t * t - t;
+        i = j;
+    }
+
+    let (n1, n2) = (a.len() as f64, b.len() as f64);
+    let n = n1 + n2;
+    let u = rank_sum - n1 * (n1 + 1.0) / 2.0;
+    let mu = n1 * n2 / 2.0;
+    let sigma = (n1 * n2 / 12.0 * ((n + 1.0) - ties / (n * (n - 1.0)))).sqrt();
+    let z = ((u - mu).abs() - 0.5).max(0.0) / sigma;
+    (u, erfc(z / std::f64::consts::SQRT_2).min(1.0))
+}
+
+/// Cliff's delta effect size for `a` relative to `b`, computed from the
+/// Mann-Whitney U statistic. Positive values mean values in `a` tend to be
+/// larger than those in `b`
+pub fn cliffs_delta(a: &[f64], b: &[f64]) -> f64 {
+    let (u, _) = mann_whitney_u(a, b);
+    2.0 * u / (a.len() * b.len()) as f64 - 1.0
+}
+
+/// Cohen's d effect size for the difference in the means of `a` and `b`, using
+/// the pooled standard deviation
+pub fn cohens_d(a: &[f64], b: &[f64]) -> f64 {
+    let (n1, n2) = (a.len() as f64, b.len() as f64);
+    let pooled = (((n1 - 1.0) * std_dev(a).powi(2)
+        + (n2 - 1.0) * std_dev(b).powi(2))
+        / (n1 + n2 - 2.0))
+        .sqrt();
+    (mean(a) - mean(b)) / pooled
+}
@@ -0,0 +1,47 @@
+use super::*;
+
+fn close(a: f64, b: f64) -> bool {
+    (a - b).abs() < 1e-8
+}
+
+#[test]
+fn test_hypergeometric_sf() {
+    let lf = LnFactorials::new(50);
+    // scipy.stats.hypergeom.sf(2, 50, 5, 10)
+    let got = lf.hypergeometric_sf(3, 50, 5, 10);
+    assert!(close(got, 0.0482603031962091), "got {got}");
+    assert!(close(lf.hypergeometric_sf(0, 50, 5, 10), 1.0));
+    assert_eq!(lf.hypergeometric_sf(6, 50, 5, 10), 0.0);
+}
+
+#[test]
+fn test_benjamini_hochberg() {
+    let got = benjamini_hochberg(&[0.01, 0.04, 0.03, 0.2]);
+    let want = [0.04, 0.16 / 3.0, 0.16 / 3.0, 0.2];
+    for (g, w) in got.iter().zip(want) {
+        assert!(close(*g, w), "got {got:?}");
+    }
+}
+
+#[test]
+fn test_two_sample() {
+    let a = [1.2, 3.4, 0.5, 2.2, 5.1, 2.2, 0.9, 4.4];
+    let b = [0.1, 0.3, 1.1, 0.7, 2.2, -0.4, 0.8];
+    let approx = |a: f64, b: f64| (a - b).abs() < 1e-6;
+
+    assert!(close(median(&a), 2.2));
+    assert!(close(rms(&a), 2.9391750543307213));
+    assert!(approx(erfc(0.7), 0.32219880616258156));
+    assert!(approx(erfc(-1.3), 1.9340079449406524));
+
+    let (d, p) = ks_2samp(&a, &b);
+    assert!(close(d, 0.6071428571428571), "got {d}");
+    assert!(close(p, 0.07528772410866562), "got {p}");
+
+    let (u, p) = mann_whitney_u(&a, &b);
+    assert!(close(u, 48.0), "got {u}");
+    assert!(approx(p, 0.023525598678430206), "got {p}");
+
+    assert!(close(cliffs_delta(&a, &b), 0.7142857142857142));
+    assert!(close(cohens_d(&a, &b), 1.3328340482755008));
+}
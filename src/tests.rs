@@ -0,0 +1,48 @@
+use super::*;
+
+#[test]
+fn test_filter_dataset() {
+    let ds: serde_json::Value = serde_json::from_str(
+        r#"{
+            "type": "OptimizationResultCollection",
+            "entries": {
+                "https://api.qcarchive.molssi.org:443/": [
+                    {"type": "hessian", "record_id": 1, "cmiles": "C"},
+                    {"type": "hessian", "record_id": 2, "cmiles": "CC"},
+                    {"type": "hessian", "record_id": "3", "cmiles": "CCC"}
+                ]
+            },
+            "provenance": {}
+        }"#,
+    )
+    .unwrap();
+    let keep = ["2", "3"].map(String::from).into_iter().collect();
+    let got = filter_dataset(&ds, &keep);
+
+    let entries = &got["entries"]["https://api.qcarchive.molssi.org:443/"];
+    let ids: Vec<_> = entries
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| e["record_id"].to_string())
+        .collect();
+    assert_eq!(ids, ["2", "\"3\""]);
+    assert_eq!(entries[0]["type"], "hessian");
+    assert_eq!(got["type"], ds["type"]);
+    assert_eq!(got["provenance"], ds["provenance"]);
+}
+
+#[test]
+fn test_dataset_smiles() {
+    let ds: serde_json::Value = serde_json::from_str(
+        r#"{"entries": {"a": [
+            {"record_id": 1, "cmiles": "C"},
+            {"record_id": 2, "cmiles": "CC"}
+        ]}}"#,
+    )
+    .unwrap();
+    let got = dataset_smiles(&ds).unwrap();
+    assert_eq!(got.len(), 2);
+    assert_eq!(got["2"], "CC");
+    assert!(dataset_smiles(&serde_json::json!({"entries": 1})).is_err());
+}